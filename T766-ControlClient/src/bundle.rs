@@ -0,0 +1,117 @@
+use std::fs;
+use std::io::Cursor;
+use serde::{Deserialize, Serialize};
+use tar::Archive;
+use tempfile::TempDir;
+use ed25519_dalek::{Signature, VerifyingKey};
+use log::warn;
+use crate::config::{bundle_path, CONFIG};
+
+/// Cache slot holding the most recently downloaded bundle.
+pub const LATEST: &str = "bundle";
+/// Cache slot holding the last bundle that applied cleanly, used when offline.
+pub const LAST_GOOD: &str = "last-good-bundle";
+
+#[derive(Serialize, Deserialize)]
+struct BundleMeta {
+    etag: Option<String>,
+    signature: String,
+}
+
+/// What the server built the bundle from, shipped in it as `bundle.json`.
+#[derive(Deserialize, Default)]
+pub struct BundleInfo {
+    /// Manifest repository commit, if the server serves from git.
+    #[serde(default)]
+    pub commit: Option<String>,
+}
+
+impl BundleInfo {
+    /// Read `bundle.json` from an unpacked bundle. Older bundles don't have one.
+    pub fn load(dir: &std::path::Path) -> BundleInfo {
+        fs::read_to_string(dir.join("bundle.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| warn!("Invalid bundle.json: {}", e)).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Check `data` against the hex-encoded `signature`, made with the server key
+/// pinned as `manifest_public_key`.
+pub fn verify_signature(data: &[u8], signature: &str) -> Result<(), String> {
+    let key_bytes: [u8; 32] = hex::decode(CONFIG.manifest_public_key.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or("manifest_public_key is missing or not a 32-byte hex key")?;
+    let key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|e| format!("Invalid manifest public key: {}", e))?;
+    let signature = hex::decode(signature.trim())
+        .ok()
+        .and_then(|b| Signature::from_slice(&b).ok())
+        .ok_or("Malformed signature")?;
+    key.verify_strict(data, &signature)
+        .map_err(|_| "Signature does not match pinned key".to_string())
+}
+
+/// A manifest tarball together with the server's signature over it.
+pub struct Bundle {
+    pub tarball: Vec<u8>,
+    pub signature: String,
+    pub etag: Option<String>,
+}
+
+impl Bundle {
+    /// Check the bundle against the pinned server key before anything touches it.
+    pub fn verify(&self) -> Result<(), String> {
+        verify_signature(&self.tarball, &self.signature)
+            .map_err(|e| format!("Manifest bundle rejected: {}", e))
+    }
+
+    /// Load a cached bundle, discarding it if it no longer verifies.
+    pub fn load(slot: &str) -> Option<Bundle> {
+        let meta = fs::read_to_string(bundle_path(slot, "json")).ok()?;
+        let meta: BundleMeta = serde_json::from_str(&meta).ok()?;
+        let tarball = fs::read(bundle_path(slot, "tar")).ok()?;
+        let bundle = Bundle { tarball, signature: meta.signature, etag: meta.etag };
+        match bundle.verify() {
+            Ok(()) => Some(bundle),
+            Err(e) => {
+                warn!("Ignoring cached {} bundle: {}", slot, e);
+                None
+            }
+        }
+    }
+
+    /// Persist the bundle to a cache slot, replacing whatever was there.
+    pub fn save(&self, slot: &str) -> Result<(), String> {
+        let tar_path = bundle_path(slot, "tar");
+        let meta_path = bundle_path(slot, "json");
+        if let Some(parent) = tar_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create state directory: {}", e))?;
+        }
+
+        let meta = serde_json::to_string(&BundleMeta {
+            etag: self.etag.clone(),
+            signature: self.signature.clone(),
+        }).map_err(|e| e.to_string())?;
+
+        let temp_path = tar_path.with_extension("tmp");
+        fs::write(&temp_path, &self.tarball)
+            .map_err(|e| format!("Failed to write cached bundle: {}", e))?;
+        fs::rename(&temp_path, &tar_path)
+            .map_err(|e| format!("Failed to rename cached bundle: {}", e))?;
+        fs::write(&meta_path, meta)
+            .map_err(|e| format!("Failed to write bundle metadata: {}", e))
+    }
+
+    pub fn unpack(&self) -> Result<TempDir, String> {
+        let temp_dir = TempDir::new().map_err(|e| e.to_string())?;
+        let mut archive = Archive::new(Cursor::new(&self.tarball));
+        archive.unpack(temp_dir.path()).map_err(|e| {
+            format!("Error opening tarball: {e}\nServer response: {:?}",
+                    String::from_utf8_lossy(&self.tarball))
+        })?;
+        Ok(temp_dir)
+    }
+}
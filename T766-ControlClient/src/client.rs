@@ -1,17 +1,58 @@
-use std::io::Cursor;
-use tar::Archive;
-use tempfile::TempDir;
+use serde::Deserialize;
 use serde_json;
-use log::{error, info};
-use std::sync::LazyLock;
-use crate::config::{load_config, ClientConfig};
+use log::{error, info, warn};
+use crate::bundle::{self, Bundle};
+use crate::config::CONFIG;
+use crate::pause::PauseStatus;
 use crate::puppet::ApplyResult;
 
 const MAX_LOG_BYTES: usize = 50_000;
+const SIGNATURE_HEADER: &str = "x-manifest-signature";
 
-static CONFIG: LazyLock<ClientConfig> = LazyLock::new(|| {
-    load_config().expect("failed to load config")
-});
+/// Why a request to the control node failed.
+#[derive(Debug)]
+pub enum RequestError {
+    /// The server couldn't be contacted, or failed while handling the request.
+    Unreachable(String),
+    /// The server answered, but refused the request or sent something we can't use.
+    Rejected(String),
+    /// The server refused the request with this HTTP status code.
+    Status(i32),
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Unreachable(e) | RequestError::Rejected(e) => f.write_str(e),
+            RequestError::Status(c) => write!(f, "status code {}", c),
+        }
+    }
+}
+
+impl From<String> for RequestError {
+    fn from(e: String) -> Self {
+        RequestError::Rejected(e)
+    }
+}
+
+impl From<&str> for RequestError {
+    fn from(e: &str) -> Self {
+        RequestError::Rejected(e.to_string())
+    }
+}
+
+impl From<RequestError> for String {
+    fn from(e: RequestError) -> Self {
+        e.to_string()
+    }
+}
+
+/// What the server confirmed it stored from a report.
+#[derive(Debug, Default, Deserialize)]
+pub struct SyncAck {
+    #[serde(default)]
+    pub checkin_ids: Vec<String>,
+}
 
 pub struct Client;
 
@@ -21,55 +62,114 @@ impl Client {
     }
 
     /// Try a request against the primary URL, then fall back to the fallback URL.
-    fn request_with_fallback<F, T>(&self, make_request: F) -> Result<T, String>
+    ///
+    /// The result is only `Unreachable` if neither server could be contacted.
+    fn request_with_fallback<F, T>(&self, make_request: F) -> Result<T, RequestError>
     where
-        F: Fn(&str) -> Result<T, String>,
+        F: Fn(&str) -> Result<T, RequestError>,
     {
-        match make_request(&CONFIG.primary_url) {
+        let primary_err = match make_request(&CONFIG.primary_url) {
             Ok(v) => return Ok(v),
-            Err(e) => error!("Primary connection failed: {}", e),
-        }
+            Err(e) => { error!("Primary connection failed: {}", e); e }
+        };
         info!("Falling back to remote control node...");
-        make_request(&CONFIG.fallback_url)
-            .map_err(|e| { error!("Fallback connection failed: {}", e); e })
+        make_request(&CONFIG.fallback_url).map_err(|e| {
+            error!("Fallback connection failed: {}", e);
+            match (primary_err, e) {
+                (p @ (RequestError::Rejected(_) | RequestError::Status(_)), RequestError::Unreachable(_)) => p,
+                (_, e) => e,
+            }
+        })
     }
 
-    fn http_get(url: &str) -> Result<Vec<u8>, String> {
-        let response = minreq::get(url)
+    /// Identify this host to the server with its device token, if it has one.
+    fn authorized(request: minreq::Request) -> minreq::Request {
+        match CONFIG.device_token.as_deref().filter(|t| !t.is_empty()) {
+            Some(token) => request.with_header("Authorization", format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    /// GET `url` with the query `params`, which are percent-encoded.
+    fn http_get(url: &str, params: &[(&str, &str)], if_none_match: Option<&str>) -> Result<minreq::Response, RequestError> {
+        let mut request = params.iter().fold(Self::authorized(minreq::get(url)), |r, (k, v)| r.with_param(*k, *v))
             .with_header("Accept-Encoding", "identity")
-            .with_timeout(20)
-            .send()
-            .map_err(|e| e.to_string())?;
-        if response.status_code != 200 {
-            return Err(format!("status code {}", response.status_code));
+            .with_timeout(20);
+        if let Some(etag) = if_none_match {
+            request = request.with_header("If-None-Match", etag);
         }
-        Ok(response.into_bytes())
+        let response = request.send().map_err(|e| RequestError::Unreachable(e.to_string()))?;
+        Self::check_status(response.status_code, &[200, 304])?;
+        Ok(response)
     }
 
-    fn http_post(url: &str, body: &str) -> Result<String, String> {
-        let response = minreq::post(url)
+    fn check_status(status_code: i32, expected: &[i32]) -> Result<(), RequestError> {
+        match status_code {
+            c if expected.contains(&c) => Ok(()),
+            c if c >= 500 => Err(RequestError::Unreachable(format!("status code {}", c))),
+            c => Err(RequestError::Status(c)),
+        }
+    }
+
+    fn http_post(url: &str, body: &str) -> Result<String, RequestError> {
+        let response = Self::authorized(minreq::post(url))
             .with_header("Accept-Encoding", "identity")
             .with_timeout(20)
             .with_body(body)
             .send()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| RequestError::Unreachable(e.to_string()))?;
+        Self::check_status(response.status_code, &[200, 204])?;
         Ok(String::from_utf8_lossy(&response.into_bytes()).into_owned())
     }
 
-    pub fn manifests(&self) -> Result<TempDir, String> {
-        let tarball = self.request_with_fallback(|base| {
+    /// Fetch the current bundle, reusing the cached copy when the server says it's unchanged.
+    pub fn manifests(&self) -> Result<Bundle, RequestError> {
+        let cached = Bundle::load(bundle::LATEST);
+        let cached_etag = cached.as_ref().and_then(|b| b.etag.clone());
+
+        let fetched = self.request_with_fallback(|base| {
+            // The hostname picks the group's bundle when the server doesn't require tokens.
             let url = format!("{}manifests", base);
-            info!("{}", url);
-            Self::http_get(&url)
+            let hostname = crate::host::hostname();
+            info!("{}?hostname={}", url, hostname);
+            let response = Self::http_get(&url, &[("hostname", &hostname)], cached_etag.as_deref())?;
+            if response.status_code == 304 {
+                info!("Manifests unchanged, using cached bundle");
+                return Ok(None);
+            }
+            let signature = response.headers.get(SIGNATURE_HEADER)
+                .cloned()
+                .ok_or("Server did not sign the manifest bundle")?;
+            let etag = response.headers.get("etag").cloned();
+            let bundle = Bundle { tarball: response.into_bytes(), signature, etag };
+            bundle.verify()?;
+            Ok(Some(bundle))
         })?;
 
-        let temp_dir = TempDir::new().map_err(|e| e.to_string())?;
-        let mut archive = Archive::new(Cursor::new(&tarball));
-        archive.unpack(temp_dir.path()).map_err(|e| {
-            format!("Error opening tarball: {e}\nServer response: {:?}",
-                    String::from_utf8_lossy(&tarball))
-        })?;
-        Ok(temp_dir)
+        match fetched {
+            Some(bundle) => {
+                if let Err(e) = bundle.save(bundle::LATEST) {
+                    warn!("Failed to cache manifest bundle: {}", e);
+                }
+                Ok(bundle)
+            }
+            None => cached.ok_or_else(|| "Server returned 304 but no bundle is cached".into()),
+        }
+    }
+
+    /// Ask the server whether applies are paused fleet-wide. The answer must
+    /// be signed with the same key as the bundles.
+    pub fn pause_status(&self) -> Result<PauseStatus, RequestError> {
+        self.request_with_fallback(|base| {
+            let url = format!("{}pause", base);
+            let response = Self::http_get(&url, &[], None)?;
+            let signature = response.headers.get(SIGNATURE_HEADER)
+                .ok_or("Server did not sign the pause status")?;
+            bundle::verify_signature(response.as_bytes(), signature)
+                .map_err(|e| format!("Pause status rejected: {}", e))?;
+            serde_json::from_slice(response.as_bytes())
+                .map_err(|e| RequestError::Rejected(format!("Invalid pause status: {}", e)))
+        })
     }
 
     fn truncate_log(log: &str) -> String {
@@ -83,18 +183,21 @@ impl Client {
         format!("[...truncated...]\n{}", &log[start..])
     }
 
-    pub fn send_status(&self, mut status: ApplyResult) -> Result<String, String> {
+    pub fn send_status(&self, mut status: ApplyResult) -> Result<SyncAck, RequestError> {
         status.logs = Self::truncate_log(&status.logs);
-        status.checkin_logs = status.checkin_logs
-            .into_iter()
-            .map(|l| Self::truncate_log(&l))
-            .collect();
+        for checkin in &mut status.checkins {
+            checkin.log = Self::truncate_log(&checkin.log);
+        }
 
-        let body = serde_json::to_string(&status).map_err(|e| e.to_string())?;
+        let body = serde_json::to_string(&status).map_err(|e| RequestError::Rejected(e.to_string()))?;
 
-        self.request_with_fallback(|base| {
+        let response = self.request_with_fallback(|base| {
             let url = format!("{}puppet-sync", base);
             Self::http_post(&url, &body)
-        })
+        })?;
+        Ok(serde_json::from_str(&response).unwrap_or_else(|e| {
+            warn!("Unexpected response from control node ({}): {:?}", e, response);
+            SyncAck::default()
+        }))
     }
 }
@@ -1,15 +1,49 @@
 use std::fs;
 use std::path::PathBuf;
 use toml;
-use crate::host::os;
-use serde::Deserialize;
+use crate::host::{hostname, os};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::io::Write;
+use std::sync::LazyLock;
 use log::info;
 
+pub static CONFIG: LazyLock<ClientConfig> = LazyLock::new(|| {
+    load_config().expect("failed to load config")
+});
+
 #[derive(Deserialize)]
 pub struct ClientConfig {
     pub primary_url: String,
     pub fallback_url: String,
+    /// Hex-encoded ed25519 key the control server signs manifest bundles with.
+    pub manifest_public_key: String,
+    /// Cron expression for scheduled runs, in local time.
+    #[serde(default = "default_schedule")]
+    pub schedule: String,
+    /// Each host delays its runs by a fixed amount up to this many seconds.
+    #[serde(default)]
+    pub splay_seconds: u64,
+    /// Issued on the control server's Devices page and sent with every request.
+    #[serde(default)]
+    pub device_token: Option<String>,
+}
+
+/// Schedule used when the config doesn't set one or sets one that doesn't parse.
+pub const DEFAULT_SCHEDULE: &str = "0,30 * * * *";
+
+fn default_schedule() -> String {
+    DEFAULT_SCHEDULE.to_string()
+}
+
+/// Expand a standard five-field cron expression to the seconds-first form the
+/// `cron` crate expects. Six- and seven-field expressions pass through as-is.
+pub fn cron_expression(schedule: &str) -> String {
+    if schedule.split_whitespace().count() == 5 {
+        format!("0 {}", schedule.trim())
+    } else {
+        schedule.trim().to_string()
+    }
 }
 
 macro_rules! conf_file {
@@ -45,37 +79,101 @@ pub fn state_file() -> PathBuf {
     conf_file!("last_run.txt")
 }
 
-pub fn checkin_logs() -> Result<Vec<String>, Vec<String>> {
-    let path = conf_file!("checkin-logs");
-    let file = fs::read_to_string(&path)
-        .map_err(|e| format!("Failed to read {:?}: {}", path, e));
-    match file {
-        Ok(file) => {
-            let logs = file.split("\n\n\n")
-                .filter(|s| !s.is_empty())
-                .map(String::from)
-                .collect();
-            info!("Logs: {:?}", logs);
-            Ok(logs)
-        }
-        Err(e) => Err(vec![e.to_string()]),
+pub fn outbox_file() -> PathBuf {
+    conf_file!("outbox.jsonl")
+}
+
+pub fn pause_file() -> PathBuf {
+    conf_file!("pause.json")
+}
+
+pub fn bundle_path(slot: &str, extension: &str) -> PathBuf {
+    conf_file!(format!("{}.{}", slot, extension))
+}
+
+/// A single checkin written by the checkin app, with an ID derived from its
+/// content so re-sending it can never create a second record on the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckinEntry {
+    pub id: String,
+    pub log: String,
+}
+
+impl CheckinEntry {
+    fn new(log: &str) -> Self {
+        let digest = Sha256::digest(format!("{}\n{}", hostname(), log));
+        CheckinEntry { id: hex::encode(&digest[..16]), log: log.to_string() }
     }
 }
 
-pub fn clear_logs() -> std::io::Result<()> {
+fn split_checkins(contents: &str) -> Vec<CheckinEntry> {
+    contents.split("\n\n\n")
+        .filter(|s| !s.is_empty())
+        .map(CheckinEntry::new)
+        .collect()
+}
+
+/// Where `archive_logs` moves `checkin-logs` while it sorts it, so the
+/// checkin app can keep appending to a fresh file in the meantime.
+fn archiving_path() -> PathBuf {
+    conf_file!("checkin-logs.archiving")
+}
+
+pub fn checkin_logs() -> Result<Vec<CheckinEntry>, Vec<String>> {
+    // Checkins left behind by an interrupted archive still need sending,
+    // even if nothing has been written to `checkin-logs` since.
+    let leftover = fs::read_to_string(archiving_path()).unwrap_or_default();
     let path = conf_file!("checkin-logs");
-    let old_path = conf_file!("old-checkin-logs");
+    let file = match fs::read_to_string(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !leftover.is_empty() => String::new(),
+        Err(e) => return Err(vec![format!("Failed to read {:?}: {}", path, e)]),
+    };
+    let logs = split_checkins(&(leftover + &file));
+    info!("Logs: {:?}", logs);
+    Ok(logs)
+}
 
-    if let Ok(contents) = fs::read_to_string(&path) {
-        let mut old_file = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&old_path)?;
+/// Move checkins the server has acknowledged into `old-checkin-logs`,
+/// leaving everything else to be sent again next run.
+///
+/// The checkin app appends to `checkin-logs` at any time, so rather than
+/// rewriting it in place, it's moved aside first and the unacknowledged
+/// checkins are appended back to whatever has been written since.
+pub fn archive_logs(acked: &[String]) -> std::io::Result<()> {
+    if acked.is_empty() {
+        return Ok(());
+    }
+    let path = conf_file!("checkin-logs");
+    let old_path = conf_file!("old-checkin-logs");
+    let archiving_path = archiving_path();
 
-        writeln!(old_file, "{}", contents)?;
+    // A file left by an interrupted archive is finished first; what's in
+    // `checkin-logs` now is sorted next time.
+    if !archiving_path.exists() {
+        match fs::rename(&path, &archiving_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        }
     }
 
-    fs::write(&path, "")
+    let contents = fs::read_to_string(&archiving_path)?;
+    let (done, pending): (Vec<_>, Vec<_>) = split_checkins(&contents)
+        .into_iter()
+        .partition(|entry| acked.contains(&entry.id));
+
+    let append = |path: &PathBuf, entries: &[CheckinEntry]| -> std::io::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        let text: String = entries.iter().map(|e| format!("{}\n\n\n", e.log)).collect();
+        file.write_all(text.as_bytes())
+    };
+    append(&old_path, &done)?;
+    append(&path, &pending)?;
+    fs::remove_file(&archiving_path)
 }
 
 pub fn load_config() -> Result<ClientConfig, String> {
@@ -0,0 +1,191 @@
+use std::path::Path;
+use std::process::Command;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sysinfo::{CpuRefreshKind, Disks, Networks, System};
+use crate::host::os;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: Option<String>,
+    pub ips: Vec<String>,
+}
+
+/// Hardware and OS inventory sent with each sync.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HostFacts {
+    /// "facter" if facter supplied the facts, otherwise "native".
+    pub source: String,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    pub kernel: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_count: Option<u32>,
+    pub memory_total_bytes: Option<u64>,
+    pub disk_total_bytes: Option<u64>,
+    pub disk_free_bytes: Option<u64>,
+    pub serial_number: Option<String>,
+    pub interfaces: Vec<NetworkInterface>,
+    /// Full-charge capacity as a percentage of design capacity.
+    pub battery_health_percent: Option<f64>,
+    pub uptime_seconds: Option<u64>,
+}
+
+/// Run a helper program without flashing a console window, returning trimmed stdout.
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let mut command = if os() == "windows" {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", program]);
+        cmd
+    } else {
+        Command::new(program)
+    };
+    command.args(args);
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        use winapi::um::winbase::CREATE_NO_WINDOW;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!stdout.is_empty()).then_some(stdout)
+}
+
+fn powershell(script: &str) -> Option<String> {
+    run("powershell", &["-NoProfile", "-NonInteractive", "-Command", script])
+}
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    let value = std::fs::read_to_string(path).ok()?.trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn serial_number() -> Option<String> {
+    if os() == "windows" {
+        powershell("(Get-CimInstance Win32_BIOS).SerialNumber")
+    } else {
+        read_trimmed("/sys/class/dmi/id/product_serial")
+    }
+}
+
+fn battery_health() -> Option<f64> {
+    let (full, design) = if os() == "windows" {
+        let full = powershell(
+            "(Get-CimInstance -Namespace root\\wmi -ClassName BatteryFullChargedCapacity | Select-Object -First 1).FullChargedCapacity",
+        )?;
+        let design = powershell(
+            "(Get-CimInstance -Namespace root\\wmi -ClassName BatteryStaticData | Select-Object -First 1).DesignedCapacity",
+        )?;
+        (full, design)
+    } else {
+        let battery = std::fs::read_dir("/sys/class/power_supply").ok()?
+            .flatten()
+            .map(|e| e.path())
+            .find(|p| p.file_name().is_some_and(|n| n.to_string_lossy().starts_with("BAT")))?;
+        ["energy", "charge"].iter().find_map(|kind| {
+            Some((
+                read_trimmed(battery.join(format!("{}_full", kind)))?,
+                read_trimmed(battery.join(format!("{}_full_design", kind)))?,
+            ))
+        })?
+    };
+
+    let full: f64 = full.parse().ok()?;
+    let design: f64 = design.parse().ok()?;
+    (design > 0.0).then(|| (full / design * 1000.0).round() / 10.0)
+}
+
+/// Facts we can gather without facter.
+fn native() -> HostFacts {
+    let mut system = System::new();
+    system.refresh_memory();
+    system.refresh_cpu_list(CpuRefreshKind::nothing());
+
+    let root = if os() == "windows" { "C:\\" } else { "/" };
+    let disks = Disks::new_with_refreshed_list();
+    let root_disk = disks.list().iter().find(|d| d.mount_point() == Path::new(root));
+
+    let mut interfaces: Vec<NetworkInterface> = Networks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .map(|(name, data)| NetworkInterface {
+            name: name.clone(),
+            mac: Some(data.mac_address().to_string()),
+            ips: data.ip_networks().iter().map(|n| n.addr.to_string()).collect(),
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+    HostFacts {
+        source: "native".to_string(),
+        os_name: System::name(),
+        os_version: System::long_os_version().or_else(System::os_version),
+        kernel: System::kernel_version(),
+        cpu_model: system.cpus().first().map(|c| c.brand().trim().to_string()),
+        cpu_count: Some(system.cpus().len() as u32),
+        memory_total_bytes: Some(system.total_memory()),
+        disk_total_bytes: root_disk.map(|d| d.total_space()),
+        disk_free_bytes: root_disk.map(|d| d.available_space()),
+        serial_number: serial_number(),
+        interfaces,
+        battery_health_percent: battery_health(),
+        uptime_seconds: Some(System::uptime()),
+    }
+}
+
+/// Overlay whatever facter reports on top of the native facts.
+fn apply_facter(facts: &mut HostFacts, v: &Value) {
+    let str_at = |ptr: &str| v.pointer(ptr).and_then(Value::as_str).map(String::from);
+    let u64_at = |ptr: &str| v.pointer(ptr).and_then(Value::as_u64);
+
+    facts.source = "facter".to_string();
+    facts.os_name = str_at("/os/name").or(facts.os_name.take());
+    facts.os_version = str_at("/os/release/full").or(facts.os_version.take());
+    facts.kernel = str_at("/kernelrelease").or(facts.kernel.take());
+    facts.cpu_model = str_at("/processors/models/0").or(facts.cpu_model.take());
+    facts.cpu_count = u64_at("/processors/count").map(|c| c as u32).or(facts.cpu_count);
+    facts.memory_total_bytes = u64_at("/memory/system/total_bytes").or(facts.memory_total_bytes);
+    facts.serial_number = str_at("/dmi/product/serial_number").or(facts.serial_number.take());
+    facts.uptime_seconds = u64_at("/system_uptime/seconds").or(facts.uptime_seconds);
+
+    if let Some(root) = v.pointer("/mountpoints/~1") {
+        facts.disk_total_bytes = root.get("size_bytes").and_then(Value::as_u64).or(facts.disk_total_bytes);
+        facts.disk_free_bytes = root.get("available_bytes").and_then(Value::as_u64).or(facts.disk_free_bytes);
+    }
+
+    if let Some(interfaces) = v.pointer("/networking/interfaces").and_then(Value::as_object) {
+        facts.interfaces = interfaces.iter()
+            .map(|(name, iface)| {
+                let ips = ["bindings", "bindings6"].iter()
+                    .filter_map(|key| iface.get(*key).and_then(Value::as_array))
+                    .flatten()
+                    .filter_map(|b| b.get("address").and_then(Value::as_str).map(String::from))
+                    .collect();
+                NetworkInterface {
+                    name: name.clone(),
+                    mac: iface.get("mac").and_then(Value::as_str).map(String::from),
+                    ips,
+                }
+            })
+            .collect();
+    }
+}
+
+/// Collect facts, preferring `facter --json` and falling back to native probes.
+pub fn collect() -> HostFacts {
+    let mut facts = native();
+    match run("facter", &["--json"]).map(|out| serde_json::from_str::<Value>(&out)) {
+        Some(Ok(v)) => apply_facter(&mut facts, &v),
+        Some(Err(e)) => warn!("Failed to parse facter output: {}", e),
+        None => warn!("facter unavailable, using native facts"),
+    }
+    facts
+}
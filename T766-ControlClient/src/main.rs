@@ -1,22 +1,48 @@
 #![cfg_attr(all(windows, not(feature = "run")), windows_subsystem = "windows")]
 use crate::puppet::PuppetClient;
 
+mod bundle;
 mod client;
+mod facts;
 mod host;
+mod outbox;
+mod pause;
 mod puppet;
+mod report;
 mod config;
 
-use chrono::{Local, DateTime, Timelike, Duration};
-use std::{thread, fs, path::PathBuf, sync::Arc, sync::atomic::{AtomicBool, Ordering}};
+use chrono::{Local, DateTime, Duration};
+use cron::Schedule;
+use sha2::{Digest, Sha256};
+use std::{thread, fs, path::PathBuf, str::FromStr, sync::Arc, sync::LazyLock, sync::atomic::{AtomicBool, Ordering}};
 use std::process::exit;
 use log::{info, error, warn};
-use config::{log_path, state_file};
+use config::{cron_expression, log_path, state_file, CONFIG, DEFAULT_SCHEDULE};
 
 const CATCH_UP_WINDOW_MINUTES: i64 = 15;
 const MAX_CONSECUTIVE_FAILURES: u32 = 5;
 const MIN_BACKOFF_SECONDS: u64 = 30;
 const MAX_BACKOFF_SECONDS: u64 = 300;
 
+/// The schedule runs actually follow, which is also what reports carry.
+static SCHEDULE: LazyLock<Schedule> = LazyLock::new(|| {
+    Schedule::from_str(&cron_expression(&CONFIG.schedule)).unwrap_or_else(|e| {
+        error!("Invalid schedule {:?} ({}), using {:?}", CONFIG.schedule, e, DEFAULT_SCHEDULE);
+        Schedule::from_str(&cron_expression(DEFAULT_SCHEDULE)).unwrap()
+    })
+});
+
+/// This host's fixed offset from the schedule, derived from its hostname so it
+/// stays put across restarts while spreading the fleet over `splay_seconds`.
+static SPLAY: LazyLock<Duration> = LazyLock::new(|| {
+    if CONFIG.splay_seconds == 0 {
+        return Duration::zero();
+    }
+    let digest = Sha256::digest(host::hostname());
+    let seed = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    Duration::seconds((seed % (CONFIG.splay_seconds + 1)) as i64)
+});
+
 fn load_last_run() -> Option<DateTime<Local>> {
     let path = state_file();
     let content = fs::read_to_string(&path).ok()?;
@@ -50,30 +76,16 @@ fn save_last_run(time: DateTime<Local>) -> Result<(), String> {
 
 fn get_last_scheduled_run() -> DateTime<Local> {
     let now = Local::now();
-    let mut target = now.with_second(0).unwrap().with_nanosecond(0).unwrap();
-    let minute = now.minute();
-
-    if minute < 30 {
-        target = target.with_minute(0).unwrap();
-    } else {
-        target = target.with_minute(30).unwrap();
-    }
-
-    target
+    SCHEDULE.after(&(now - *SPLAY)).next_back()
+        .map(|t| t + *SPLAY)
+        .unwrap_or(now)
 }
 
 fn get_next_scheduled_run() -> DateTime<Local> {
     let now = Local::now();
-    let mut target = now.with_second(0).unwrap().with_nanosecond(0).unwrap();
-    let minute = now.minute();
-
-    if minute < 30 {
-        target = target.with_minute(30).unwrap();
-    } else {
-        target = target.with_minute(0).unwrap() + Duration::hours(1);
-    }
-
-    target
+    SCHEDULE.after(&(now - *SPLAY)).next()
+        .map(|t| t + *SPLAY)
+        .unwrap_or_else(|| now + Duration::minutes(30))
 }
 
 fn run_sync(client: &PuppetClient) -> Result<(), String> {
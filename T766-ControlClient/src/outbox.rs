@@ -0,0 +1,91 @@
+use std::fs;
+use std::io::Write;
+use log::{info, warn};
+use crate::client::{Client, RequestError};
+use crate::config::{archive_logs, outbox_file};
+use crate::puppet::ApplyResult;
+
+/// Oldest reports are dropped past this point so a long outage can't fill the disk.
+const MAX_QUEUED_REPORTS: usize = 200;
+
+fn write_queue(lines: &[&str]) -> Result<(), String> {
+    let path = outbox_file();
+    let temp_path = path.with_extension("tmp");
+    let mut contents = lines.join("\n");
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    fs::write(&temp_path, contents)
+        .and_then(|_| fs::rename(&temp_path, &path))
+        .map_err(|e| format!("Failed to rewrite outbox: {}", e))
+}
+
+/// Append a report to the on-disk outbox. Reports stay there until the server accepts them.
+pub fn push(result: &ApplyResult) -> Result<(), String> {
+    let path = outbox_file();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create state directory: {}", e))?;
+    }
+    let line = serde_json::to_string(result).map_err(|e| e.to_string())?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open outbox {:?}: {}", path, e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write outbox: {}", e))?;
+    drop(file);
+
+    let contents = fs::read_to_string(&path).unwrap_or_default();
+    let queued: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+    if queued.len() > MAX_QUEUED_REPORTS {
+        warn!("Outbox full, dropping {} oldest report(s)", queued.len() - MAX_QUEUED_REPORTS);
+        write_queue(&queued[queued.len() - MAX_QUEUED_REPORTS..])?;
+    }
+    Ok(())
+}
+
+/// Send queued reports oldest-first, stopping at the first one the server doesn't take.
+/// Only reports the server says are malformed are dropped.
+/// Checkins are archived as soon as the server acknowledges them.
+pub fn flush(client: &Client) -> Result<String, String> {
+    let path = outbox_file();
+    let Ok(contents) = fs::read_to_string(&path) else { return Ok("Nothing to deliver".to_string()) };
+    let queued: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+    if queued.len() > 1 {
+        info!("Delivering {} queued report(s)...", queued.len());
+    }
+
+    let mut delivered = 0;
+    let mut acked = 0;
+    for (i, line) in queued.iter().enumerate() {
+        let result: ApplyResult = match serde_json::from_str(line) {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Dropping unreadable queued report: {}", e);
+                continue;
+            }
+        };
+        match client.send_status(result) {
+            Ok(ack) => {
+                delivered += 1;
+                acked += ack.checkin_ids.len();
+                if let Err(e) = archive_logs(&ack.checkin_ids) {
+                    warn!("Failed to archive acknowledged checkins: {}", e);
+                }
+            }
+            // The report itself is malformed and would never be accepted. Anything
+            // else, such as a missing token, may clear up, so the report waits.
+            Err(RequestError::Status(c @ (400 | 422))) => {
+                warn!("Server rejected queued report as invalid (status code {}), dropping it", c);
+            }
+            Err(e) => {
+                write_queue(&queued[i..])?;
+                return Err(format!("Report queued for retry, {} pending: {}", queued.len() - i, e));
+            }
+        }
+    }
+
+    write_queue(&[])?;
+    Ok(format!("Delivered {} report(s), {} checkin(s) acknowledged", delivered, acked))
+}
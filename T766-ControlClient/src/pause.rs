@@ -0,0 +1,57 @@
+use std::fs;
+use chrono::{DateTime, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::config::pause_file;
+
+/// The server's fleet-wide pause switch, as served from `/pause`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PauseStatus {
+    pub paused: bool,
+    /// Unix time the pause lifts on its own.
+    #[serde(default)]
+    pub until: Option<i64>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Unix time the server gave this answer.
+    #[serde(default)]
+    pub issued: i64,
+}
+
+impl PauseStatus {
+    /// Whether the pause still holds. A timed pause lifts on schedule even
+    /// if the server can't be reached to say so.
+    pub fn is_active(&self) -> bool {
+        self.paused && self.until.is_none_or(|until| until > Local::now().timestamp())
+    }
+
+    /// Why nothing was applied, for the log and the report.
+    pub fn describe(&self) -> String {
+        let mut text = "Applies paused by the control server".to_string();
+        if let Some(until) = self.until.and_then(|t| DateTime::from_timestamp(t, 0)) {
+            text.push_str(&format!(" until {}", until.with_timezone(&Local).format("%Y-%m-%d %H:%M")));
+        }
+        if let Some(reason) = self.reason.as_deref().filter(|r| !r.is_empty()) {
+            text.push_str(&format!(": {}", reason));
+        }
+        text
+    }
+
+    /// The last status the server sent, so a pause holds while it's unreachable.
+    pub fn load() -> PauseStatus {
+        fs::read_to_string(pause_file())
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| warn!("Ignoring cached pause status: {}", e)).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = pause_file();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create state directory: {}", e))?;
+        }
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(&path, json).map_err(|e| format!("Failed to write pause status: {}", e))
+    }
+}
@@ -1,22 +1,78 @@
-use crate::client::Client;
+use crate::bundle::{self, Bundle};
+use crate::client::{Client, RequestError};
+use crate::facts::{self, HostFacts};
 use crate::host::{hostname, os};
+use crate::outbox;
+use crate::pause::PauseStatus;
+use crate::report::{self, RunReport};
 use std::path::Path;
 use std::process::Command;
+use tempfile::TempDir;
 use log::{info, warn};
-use serde::Serialize;
-use crate::config::{checkin_logs, clear_logs};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::config::{checkin_logs, CheckinEntry};
 
 pub struct PuppetClient {
     client: Client,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ApplyResult {
     hostname: String,
-    status: String,
+    pub status: String,
     exit_code: i32,
     pub logs: String,
-    pub checkin_logs: Vec<String>
+    /// Checkins stay in `checkin-logs` until the server acknowledges their IDs.
+    #[serde(default)]
+    pub checkins: Vec<CheckinEntry>,
+    /// Client-generated ID the server uses to drop replayed duplicates.
+    report_id: String,
+    /// When the run finished, so replayed reports land at the right time.
+    ran_at: i64,
+    /// The cron schedule this host runs on, for the dashboard countdown.
+    #[serde(default)]
+    schedule: String,
+    /// Parsed from the run summary and report puppet writes, when it got that far.
+    #[serde(default)]
+    report: Option<RunReport>,
+    /// Hardware and OS facts gathered after the run.
+    #[serde(default)]
+    facts: Option<HostFacts>,
+    /// Manifest repository commit the applied bundle was built from.
+    #[serde(default)]
+    commit: Option<String>,
+    /// Applied from the last-known-good bundle because the control node was
+    /// unreachable. `status` is still the real outcome of the run.
+    #[serde(default)]
+    offline: bool,
+}
+
+impl ApplyResult {
+    fn new(status: &str, exit_code: i32, logs: String, checkins: Vec<CheckinEntry>) -> Self {
+        ApplyResult {
+            hostname: hostname(),
+            status: status.to_string(),
+            exit_code,
+            logs,
+            checkins,
+            report_id: Uuid::new_v4().to_string(),
+            ran_at: Local::now().timestamp(),
+            schedule: crate::SCHEDULE.source().to_string(),
+            report: None,
+            facts: None,
+            commit: None,
+            offline: false,
+        }
+    }
+}
+
+fn read_checkins() -> Vec<CheckinEntry> {
+    checkin_logs().unwrap_or_else(|e| {
+        warn!("Failed to read checkin logs: {:?}", e);
+        Vec::new()
+    })
 }
 
 impl PuppetClient {
@@ -26,45 +82,112 @@ impl PuppetClient {
         }
     }
 
+    /// Whether the server has paused applies, going by the last answer it
+    /// gave when it can't be reached or won't give a trustworthy one. Also
+    /// says whether it was unreachable.
+    fn check_pause(&self) -> (PauseStatus, bool) {
+        match self.client.pause_status() {
+            Ok(status) => {
+                let cached = PauseStatus::load();
+                if status.issued < cached.issued {
+                    warn!("Ignoring pause status older than the last one received");
+                    return (cached, false);
+                }
+                if let Err(e) = status.save() {
+                    warn!("Failed to cache pause status: {}", e);
+                }
+                (status, false)
+            }
+            Err(RequestError::Unreachable(e)) => {
+                warn!("Control node unreachable, using last known pause status: {}", e);
+                (PauseStatus::load(), true)
+            }
+            // This includes a 404 from servers before the pause switch, which
+            // have never sent a status, so nothing cached means not paused.
+            Err(e) => {
+                warn!("Couldn't check whether applies are paused, using last known status: {}", e);
+                (PauseStatus::load(), false)
+            }
+        }
+    }
+
+    /// Report a run that was skipped because applies are paused. Checkins
+    /// and facts still go to the server.
+    fn report_paused(&self, pause: &PauseStatus, offline: bool) -> Result<String, String> {
+        info!("{}, not applying", pause.describe());
+        let mut result = ApplyResult::new("paused", 0, pause.describe(), read_checkins());
+        result.facts = Some(facts::collect());
+        outbox::push(&result)?;
+        if offline {
+            return Ok("Applies paused and control node offline, queued report for later delivery".to_string());
+        }
+        outbox::flush(&self.client)
+    }
+
     pub fn apply(&self) -> Result<String, String> {
+        info!("Checking whether applies are paused...");
+        let (pause, offline) = self.check_pause();
+        if pause.is_active() {
+            return self.report_paused(&pause, offline);
+        }
+
         info!("Fetching manifests...");
-        let dir = self.client.manifests()?;
+        let (bundle, offline) = match self.client.manifests() {
+            Ok(bundle) => (bundle, false),
+            Err(RequestError::Unreachable(e)) => {
+                warn!("Control node unreachable, using last-known-good manifests: {}", e);
+                let bundle = Bundle::load(bundle::LAST_GOOD)
+                    .ok_or_else(|| format!("No cached manifests to apply offline: {}", e))?;
+                (bundle, true)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let dir = bundle.unpack()?;
 
         info!("Applying manifests...");
-        let manifest_path = dir.path().join("manifests");
-        let result = self.apply_dir(&manifest_path);
+        let mut result = self.apply_dir(dir.path());
+        result.commit = bundle::BundleInfo::load(dir.path()).commit;
+
+        if offline {
+            result.offline = true;
+            outbox::push(&result)?;
+            return Ok("Control node offline, queued report for later delivery".to_string());
+        }
+
+        if matches!(result.status.as_str(), "success" | "changed")
+            && let Err(e) = bundle.save(bundle::LAST_GOOD)
+        {
+            warn!("Failed to save last-known-good manifests: {}", e);
+        }
 
         info!("Returning status to server...");
-        self.client.send_status(result)
+        outbox::push(&result)?;
+        outbox::flush(&self.client)
     }
 
-    fn build_puppet_command(manifest_dir: &Path) -> Command {
-        let module_path = manifest_dir.parent()
-            .map(|p| p.join("modules"))
-            .unwrap_or_else(|| manifest_dir.join("modules"));
-
-        let manifest_str = manifest_dir.to_string_lossy();
-        let module_str = module_path.to_string_lossy();
+    /// `puppet apply` for an unpacked bundle: `manifests/`, `modules/` and,
+    /// in bundles from newer servers, `hiera.yaml` with its `data/`.
+    fn build_puppet_command(bundle_dir: &Path, report_dir: &Path) -> Command {
+        let hiera_config = bundle_dir.join("hiera.yaml");
 
-        let command = if os() == "windows" {
+        let mut command = if os() == "windows" {
             let mut cmd = Command::new("cmd");
-            cmd.args([
-                "/C", "puppet", "apply",
-                "--color=false",
-                "--modulepath", &module_str,
-                &manifest_str,
-            ]);
+            cmd.args(["/C", "puppet"]);
             cmd
         } else {
-            let mut cmd = Command::new("puppet");
-            cmd.args([
-                "apply",
-                "--color=false",
-                "--modulepath", &module_str,
-                &manifest_str,
-            ]);
-            cmd
+            Command::new("puppet")
         };
+        command
+            .args(["apply", "--color=false", "--detailed-exitcodes", "--modulepath"])
+            .arg(bundle_dir.join("modules"))
+            .arg("--lastrunfile")
+            .arg(report_dir.join(report::SUMMARY_FILE))
+            .arg("--lastrunreport")
+            .arg(report_dir.join(report::REPORT_FILE));
+        if hiera_config.is_file() {
+            command.arg("--hiera_config").arg(&hiera_config);
+        }
+        command.arg(bundle_dir.join("manifests"));
 
         #[cfg(windows)]
         {
@@ -76,18 +199,29 @@ impl PuppetClient {
         command
     }
 
-    fn apply_dir(&self, manifest_dir: &Path) -> ApplyResult {
-        let result = match Self::build_puppet_command(manifest_dir).output() {
+    fn apply_dir(&self, bundle_dir: &Path) -> ApplyResult {
+        let report_dir = match TempDir::new() {
+            Ok(d) => d,
+            Err(e) => {
+                return ApplyResult::new(
+                    "failure",
+                    -1,
+                    format!("Failed to create report directory: {}", e),
+                    Vec::new(),
+                );
+            }
+        };
+
+        let result = match Self::build_puppet_command(bundle_dir, report_dir.path()).output() {
             Ok(r) => r,
             Err(e) => {
-                warn!("Failed to run puppet apply {:?}: {}", manifest_dir, e);
-                return ApplyResult {
-                    hostname: hostname(),
-                    status: "failure".to_string(),
-                    exit_code: -1,
-                    logs: format!("Failed to run puppet apply: {}", e),
-                    checkin_logs: Vec::new(),
-                };
+                warn!("Failed to run puppet apply {:?}: {}", bundle_dir, e);
+                return ApplyResult::new(
+                    "failure",
+                    -1,
+                    format!("Failed to run puppet apply: {}", e),
+                    Vec::new(),
+                );
             }
         };
 
@@ -98,30 +232,20 @@ impl PuppetClient {
         );
 
         let exit_code = result.status.code().unwrap_or(-1);
-        let checkin = match checkin_logs() {
-            Ok(logs) => logs,
-            Err(e) => {
-                warn!("Failed to read checkin logs: {:?}", e);
-                Vec::new()
-            }
+        let checkin = read_checkins();
+
+        // With --detailed-exitcodes, 2 means changes were applied and 4 means
+        // some resources failed; 6 is both.
+        let status = match exit_code {
+            0 => "success",
+            2 => "changed",
+            6 => "changed-failure",
+            -1 => "interrupted",
+            _ => "failure",
         };
-
-        if let Err(e) = clear_logs() {
-            warn!("Failed to clear checkin logs: {}", e);
-        }
-
-        ApplyResult {
-            hostname: hostname(),
-            status: if result.status.success() {
-                "success".to_string()
-            } else if exit_code == -1 {
-                "interrupted".to_string()
-            } else {
-                "failure".to_string()
-            },
-            exit_code,
-            logs,
-            checkin_logs: checkin,
-        }
+        let mut apply_result = ApplyResult::new(status, exit_code, logs, checkin);
+        apply_result.report = RunReport::load(report_dir.path());
+        apply_result.facts = Some(facts::collect());
+        apply_result
     }
 }
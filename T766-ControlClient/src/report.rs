@@ -0,0 +1,135 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// Stops a run that touches every file on the machine from producing a huge report.
+const MAX_EVENTS: usize = 500;
+
+pub const SUMMARY_FILE: &str = "last_run_summary.yaml";
+pub const REPORT_FILE: &str = "last_run_report.yaml";
+
+/// Resource counts from `last_run_summary.yaml`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceCounts {
+    pub total: u32,
+    pub changed: u32,
+    pub failed: u32,
+    pub skipped: u32,
+    pub out_of_sync: u32,
+    pub corrective_change: u32,
+    pub restarted: u32,
+    pub failed_to_restart: u32,
+    pub scheduled: u32,
+}
+
+/// One change, failure or skip puppet recorded against a resource.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceEvent {
+    pub resource: String,
+    pub property: Option<String>,
+    pub status: String,
+    pub message: String,
+}
+
+/// Structured results of a `puppet apply`, sent alongside the raw logs.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    pub resources: ResourceCounts,
+    pub events: Vec<ResourceEvent>,
+    /// Seconds spent per resource type, plus `total` and `config_retrieval`.
+    pub timing: BTreeMap<String, f64>,
+}
+
+#[derive(Deserialize)]
+struct Summary {
+    #[serde(default)]
+    resources: ResourceCounts,
+    #[serde(default)]
+    time: BTreeMap<String, f64>,
+}
+
+// The full report is a tagged ruby object; serde_yaml ignores the tags and
+// picks out the fields below.
+#[derive(Deserialize)]
+struct Report {
+    #[serde(default)]
+    resource_statuses: HashMap<String, ResourceStatus>,
+}
+
+#[derive(Deserialize)]
+struct ResourceStatus {
+    #[serde(default)]
+    events: Vec<Event>,
+    #[serde(default)]
+    skipped: bool,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    property: Option<String>,
+    message: Option<String>,
+    status: Option<String>,
+}
+
+impl RunReport {
+    /// Read the summary and report puppet wrote into `dir`.
+    /// Returns `None` if puppet didn't get far enough to write a summary.
+    pub fn load(dir: &Path) -> Option<RunReport> {
+        let summary = fs::read_to_string(dir.join(SUMMARY_FILE)).ok()?;
+        let summary: Summary = match serde_yaml::from_str(&summary) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Failed to parse {}: {}", SUMMARY_FILE, e);
+                return None;
+            }
+        };
+
+        let mut timing = summary.time;
+        timing.remove("last_run");
+
+        let events = match fs::read_to_string(dir.join(REPORT_FILE)) {
+            Ok(report) => match serde_yaml::from_str::<Report>(&report) {
+                Ok(report) => Self::events(report),
+                Err(e) => {
+                    warn!("Failed to parse {}: {}", REPORT_FILE, e);
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+
+        Some(RunReport { resources: summary.resources, events, timing })
+    }
+
+    fn events(report: Report) -> Vec<ResourceEvent> {
+        let mut events: Vec<ResourceEvent> = Vec::new();
+        for (resource, status) in report.resource_statuses {
+            if status.skipped {
+                events.push(ResourceEvent {
+                    resource: resource.clone(),
+                    property: None,
+                    status: "skipped".to_string(),
+                    message: "Skipped".to_string(),
+                });
+            }
+            for event in status.events {
+                events.push(ResourceEvent {
+                    resource: resource.clone(),
+                    property: event.property,
+                    status: event.status.unwrap_or_else(|| "unknown".to_string()),
+                    message: event.message.unwrap_or_default(),
+                });
+            }
+        }
+
+        // Failures first so they survive truncation
+        events.sort_by(|a, b| {
+            (a.status != "failure", &a.resource).cmp(&(b.status != "failure", &b.resource))
+        });
+        events.truncate(MAX_EVENTS);
+        events
+    }
+}
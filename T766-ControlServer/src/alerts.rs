@@ -0,0 +1,251 @@
+/// Outgoing alerts, posted to each configured webhook.
+///
+/// Alerts fire on state changes only: a host's first failing run, its first
+/// good run after that, a fleet-wide failure spike, a host going silent and
+/// each step of a manifest rollout.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::config::{WebhookFormat, CONFIG};
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Alert {
+    /// A host that was healthy reported a failed run.
+    HostFailed {
+        hostname: String,
+        status: String,
+        exit_code: i32,
+        timestamp: String,
+    },
+    /// A failing host reported a good run.
+    HostRecovered {
+        hostname: String,
+        failing_since: String,
+        timestamp: String,
+    },
+    /// A large share of the fleet is failing at once.
+    FailureSpike {
+        failing_hosts: Vec<String>,
+        total_hosts: usize,
+    },
+    /// A host has missed enough scheduled syncs to count as offline.
+    HostSilent {
+        hostname: String,
+        last_seen: String,
+        missed_intervals: u32,
+    },
+    /// A new manifest commit went to the canaries.
+    RolloutStarted {
+        environment: String,
+        commit: String,
+    },
+    /// The canaries took a commit cleanly and it went to every host.
+    RolloutPromoted {
+        environment: String,
+        commit: String,
+    },
+    /// A canary failed on a new commit, so nobody else will get it.
+    RolloutHalted {
+        environment: String,
+        commit: String,
+        hostname: String,
+        status: String,
+    },
+}
+
+impl Alert {
+    pub fn message(&self) -> String {
+        match self {
+            Alert::HostFailed { hostname, status, exit_code, .. } => format!(
+                "{} started failing ({}, exit code {})", hostname, status, exit_code
+            ),
+            Alert::HostRecovered { hostname, failing_since, .. } => format!(
+                "{} recovered (failing since {})", hostname, failing_since
+            ),
+            Alert::FailureSpike { failing_hosts, total_hosts } => format!(
+                "{} of {} hosts are failing: {}",
+                failing_hosts.len(), total_hosts, failing_hosts.join(", ")
+            ),
+            Alert::HostSilent { hostname, last_seen, missed_intervals } => format!(
+                "{} has missed {} scheduled syncs (last seen {})",
+                hostname, missed_intervals, last_seen
+            ),
+            Alert::RolloutStarted { environment, commit } => format!(
+                "Rolling out {} to the {} canaries", short(commit), environment
+            ),
+            Alert::RolloutPromoted { environment, commit } => format!(
+                "Canaries took {} cleanly; promoted to every {} host", short(commit), environment
+            ),
+            Alert::RolloutHalted { environment, commit, hostname, status } => format!(
+                "Halted rollout of {} to {}: canary {} reported {}", short(commit), environment, hostname, status
+            ),
+        }
+    }
+
+    /// Discord embed colour: red for problems, green for recoveries.
+    fn color(&self) -> u32 {
+        match self {
+            Alert::HostRecovered { .. } | Alert::RolloutPromoted { .. } => 0x2ecc71,
+            Alert::HostSilent { .. } => 0xf1c40f,
+            Alert::RolloutStarted { .. } => 0x3498db,
+            _ => 0xe74c3c,
+        }
+    }
+
+    fn payload(&self, format: WebhookFormat) -> serde_json::Value {
+        match format {
+            WebhookFormat::Json => {
+                let mut value = serde_json::to_value(self).unwrap_or_default();
+                value["message"] = self.message().into();
+                value
+            }
+            WebhookFormat::Discord => json!({
+                "username": "Control Node",
+                "embeds": [{ "description": self.message(), "color": self.color() }],
+            }),
+        }
+    }
+}
+
+/// Abbreviated commit ID for messages.
+fn short(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
+
+/// Per-host alert state, so repeated failures only alert once.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AlertState {
+    pub failing: bool,
+    /// Timestamp of the report that last changed `failing`.
+    pub since: String,
+}
+
+/// Alert state key for the fleet-wide spike; host keys are bare hostnames.
+pub const SPIKE_KEY: &str = "fleet:spike";
+
+/// Send an alert to every webhook. Returns `false` if any delivery failed.
+pub async fn send(alert: &Alert) -> bool {
+    println!("Alert: {}", alert.message());
+
+    let client = reqwest::Client::new();
+    let mut delivered = true;
+    for (index, webhook) in CONFIG.webhooks.iter().enumerate() {
+        // Webhook URLs carry their secret in the path, so only the host is logged.
+        let host = reqwest::Url::parse(&webhook.url).ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let result = client.post(&webhook.url)
+            .json(&alert.payload(webhook.format))
+            .send()
+            .await;
+        match result {
+            Ok(r) if r.status().is_success() => {}
+            Ok(r) => {
+                println!("Webhook {} ({}) returned {}", index, host, r.status());
+                delivered = false;
+            }
+            Err(e) => {
+                println!("Failed to send alert to webhook {} ({}): {}", index, host, e.without_url());
+                delivered = false;
+            }
+        }
+    }
+    delivered
+}
+
+pub fn is_failure(status: &str) -> bool {
+    matches!(status, "failure" | "changed-failure")
+}
+
+pub fn is_healthy(status: &str) -> bool {
+    matches!(status, "success" | "changed")
+}
+
+/// Update a host's alert state from a newly stored report and send whatever
+/// alerts the change calls for. Statuses that are neither, such as
+/// `paused`, leave the state alone.
+pub async fn on_report(hostname: String, status: String, exit_code: i32, timestamp: String) {
+    if let Err(e) = process_report(&hostname, &status, exit_code, &timestamp).await {
+        println!("Failed to process alerts for {}: {}", hostname, e);
+    }
+}
+
+async fn process_report(hostname: &str, status: &str, exit_code: i32, timestamp: &str) -> Result<(), String> {
+    let failing = if is_failure(status) {
+        true
+    } else if is_healthy(status) {
+        false
+    } else {
+        return Ok(());
+    };
+
+    let previous = crate::db::update_alert_state(hostname, |state| {
+        // Replays of older reports don't get to flip the state back.
+        if state.failing != failing && timestamp >= state.since.as_str() {
+            *state = AlertState { failing, since: timestamp.to_string() };
+        }
+    }).map_err(|e| e.to_string())?;
+    let Some(previous) = previous else { return Ok(()) };
+
+    let alert = if failing {
+        Alert::HostFailed {
+            hostname: hostname.to_string(),
+            status: status.to_string(),
+            exit_code,
+            timestamp: timestamp.to_string(),
+        }
+    } else {
+        Alert::HostRecovered {
+            hostname: hostname.to_string(),
+            failing_since: previous.since,
+            timestamp: timestamp.to_string(),
+        }
+    };
+
+    // A spike alert stands in for the individual failures that make it up.
+    if !check_spike().await? {
+        send(&alert).await;
+    }
+    Ok(())
+}
+
+/// Alert if the share of failing hosts crosses the spike threshold.
+/// Returns `true` if a spike alert went out.
+async fn check_spike() -> Result<bool, String> {
+    let hosts = crate::db::iter_last_seen().map_err(|e| e.to_string())?;
+    let mut failing_hosts = Vec::new();
+    for (hostname, _) in &hosts {
+        let state = crate::db::get_alert_state(hostname).map_err(|e| e.to_string())?;
+        if state.is_some_and(|s| s.failing) {
+            failing_hosts.push(hostname.clone());
+        }
+    }
+
+    let spiking = failing_hosts.len() >= CONFIG.spike_min_hosts
+        && failing_hosts.len() as f64 >= hosts.len() as f64 * CONFIG.spike_fraction;
+    let now = chrono::Local::now();
+    let changed = crate::db::update_alert_state(SPIKE_KEY, |state| {
+        if spiking == state.failing {
+            return;
+        }
+        let since = chrono::NaiveDateTime::parse_from_str(&state.since, "%Y%m%d%H%M%S")
+            .ok()
+            .and_then(|t| t.and_local_timezone(chrono::Local).earliest());
+        let cooled_down = since.is_none_or(|t| now - t >= chrono::Duration::minutes(CONFIG.alert_cooldown_minutes));
+        if spiking && !cooled_down {
+            return;
+        }
+        state.failing = spiking;
+        if spiking {
+            state.since = now.format("%Y%m%d%H%M%S").to_string();
+        }
+    }).map_err(|e| e.to_string())?;
+    if changed.is_none() {
+        return Ok(false);
+    }
+
+    if spiking {
+        send(&Alert::FailureSpike { failing_hosts, total_hosts: hosts.len() }).await;
+    }
+    Ok(spiking)
+}
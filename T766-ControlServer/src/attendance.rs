@@ -0,0 +1,125 @@
+/// Per-student attendance built from stored checkins.
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StudentAttendance {
+    pub student_id: String,
+    pub name: Option<String>,
+    pub days_attended: u32,
+    /// Estimated from the first and last checkin of each day.
+    pub hours: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AttendanceReport {
+    pub start: String,
+    pub end: String,
+    /// Days anyone checked in.
+    pub meeting_days: u32,
+    pub total_hours: f64,
+    /// Sorted by name, then ID.
+    pub students: Vec<StudentAttendance>,
+}
+
+/// Unix times from local midnight on `start` to the end of `end`, two dates
+/// (YYYY-MM-DD) that are both included.
+#[cfg(feature = "server")]
+pub fn day_range(start: &str, end: &str) -> Result<std::ops::Range<i64>, String> {
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    let parse = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date {:?}: {}", date, e));
+    let (start_date, end_date) = (parse(start)?, parse(end)?);
+    if end_date < start_date {
+        return Err("End date is before start date".to_string());
+    }
+    let midnight = |date: NaiveDate| Local.from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .map(|t| t.timestamp())
+        .ok_or_else(|| format!("No local midnight on {}", date));
+    Ok(midnight(start_date)?..midnight(end_date.succ_opt().ok_or("End date out of range")?)?)
+}
+
+/// Attendance between two dates (YYYY-MM-DD), both inclusive.
+#[cfg(feature = "server")]
+pub fn build_report(start: &str, end: &str) -> Result<AttendanceReport, String> {
+    use chrono::{Local, NaiveDate};
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+    use crate::config::CONFIG;
+
+    let checkins = crate::db::checkins_in(day_range(start, end)?, None, usize::MAX).map_err(|e| e.to_string())?;
+
+    // Checkins made under a regenerated ID count toward the person's current one.
+    let current_ids: HashMap<String, String> = crate::db::iter_people().map_err(|e| e.to_string())?
+        .into_iter()
+        .flat_map(|p| p.former_ids.into_iter().map(move |former| (former, p.id.clone())))
+        .collect();
+
+    // student -> day -> (first, last) checkin time
+    let mut spans: BTreeMap<String, BTreeMap<NaiveDate, (i64, i64)>> = BTreeMap::new();
+    let mut meeting_days = BTreeSet::new();
+    for checkin in &checkins {
+        let Some(student_id) = &checkin.student_id else { continue };
+        let student_id = current_ids.get(student_id).unwrap_or(student_id);
+        let Some(day) = chrono::DateTime::from_timestamp(checkin.timestamp, 0)
+            .map(|t| t.with_timezone(&Local).date_naive())
+        else { continue };
+        meeting_days.insert(day);
+        let span = spans.entry(student_id.clone()).or_default()
+            .entry(day)
+            .or_insert((checkin.timestamp, checkin.timestamp));
+        span.0 = span.0.min(checkin.timestamp);
+        span.1 = span.1.max(checkin.timestamp);
+    }
+
+    let people = crate::checkins::load_people_map();
+    let min_session = CONFIG.attendance.min_session_hours;
+    let mut students: Vec<StudentAttendance> = spans.into_iter()
+        .map(|(student_id, days)| {
+            let hours = days.values()
+                .map(|(first, last)| ((last - first) as f64 / 3600.0).max(min_session))
+                .sum();
+            StudentAttendance {
+                name: people.get(&student_id).cloned(),
+                student_id,
+                days_attended: days.len() as u32,
+                hours,
+            }
+        })
+        .collect();
+    students.sort_by(|a, b| (a.name.is_none(), &a.name, &a.student_id).cmp(&(b.name.is_none(), &b.name, &b.student_id)));
+
+    Ok(AttendanceReport {
+        start: start.to_string(),
+        end: end.to_string(),
+        meeting_days: meeting_days.len() as u32,
+        total_hours: students.iter().map(|s| s.hours).sum(),
+        students,
+    })
+}
+
+/// The report as CSV, one row per student.
+#[cfg(feature = "server")]
+pub fn to_csv(report: &AttendanceReport) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["student_id", "name", "days_attended", "meeting_days", "hours"])
+        .map_err(|e| e.to_string())?;
+    for student in &report.students {
+        writer.write_record([
+            student.student_id.as_str(),
+            student.name.as_deref().unwrap_or(""),
+            &student.days_attended.to_string(),
+            &report.meeting_days.to_string(),
+            &format!("{:.2}", student.hours),
+        ]).map_err(|e| e.to_string())?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+#[server]
+pub async fn get_attendance(start: String, end: String) -> Result<AttendanceReport, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    build_report(&start, &end).map_err(ServerFnError::new)
+}
@@ -0,0 +1,377 @@
+/// Dashboard logins: users with argon2-hashed passwords and cookie sessions.
+///
+/// Logging in and out goes through plain form posts to `/auth/*` so the
+/// session cookie can be `HttpOnly`. Server functions call `require_role`.
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What an account can see. Each role can do everything the ones before it can.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// The read-only sync dashboard, for the shop TV.
+    Viewer,
+    /// Sync health, logs and inventory, but not other students' checkins or attendance.
+    StudentLead,
+    /// Everything, including the roster and user accounts.
+    Mentor,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Viewer, Role::StudentLead, Role::Mentor];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Role::Viewer => "Viewer",
+            Role::StudentLead => "Student lead",
+            Role::Mentor => "Mentor",
+        }
+    }
+}
+
+/// The logged-in account.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuthUser {
+    pub username: String,
+    pub role: Role,
+}
+
+/// An account as listed on the users page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserInfo {
+    pub username: String,
+    pub role: Role,
+    pub created: i64,
+}
+
+/// Whether the person viewing the dashboard is logged in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AuthStatus {
+    LoggedIn(AuthUser),
+    LoggedOut,
+    /// No users exist yet, so the login form creates the first admin.
+    NeedsSetup,
+}
+
+#[cfg(feature = "server")]
+use axum::http::{HeaderMap, header};
+#[cfg(feature = "server")]
+use axum::response::{IntoResponse, Redirect, Response};
+#[cfg(feature = "server")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "server")]
+use crate::config::CONFIG;
+
+#[cfg(feature = "server")]
+const SESSION_COOKIE: &str = "session";
+#[cfg(feature = "server")]
+const MIN_PASSWORD_LEN: usize = 8;
+
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
+    pub username: String,
+    /// PHC string from argon2.
+    pub password_hash: String,
+    pub created: i64,
+    /// Accounts from before roles existed were all the first-run admin.
+    #[serde(default = "first_admin_role")]
+    pub role: Role,
+}
+
+#[cfg(feature = "server")]
+fn first_admin_role() -> Role {
+    Role::Mentor
+}
+
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub username: String,
+    /// Unix time the session stops being valid.
+    pub expires: i64,
+}
+
+#[cfg(feature = "server")]
+fn hash_password(password: &str) -> Result<String, String> {
+    use argon2::password_hash::{PasswordHasher, SaltString};
+    let salt = SaltString::generate(&mut rand_core::OsRng);
+    argon2::Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "server")]
+fn verify_password(password: &str, hash: &str) -> bool {
+    use argon2::password_hash::{PasswordHash, PasswordVerifier};
+    PasswordHash::new(hash)
+        .is_ok_and(|h| argon2::Argon2::default().verify_password(password.as_bytes(), &h).is_ok())
+}
+
+/// Sessions are stored by the hash of their token, so a copy of the
+/// database can't be used to log in.
+#[cfg(feature = "server")]
+fn token_key(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Start a session for `username` and return its token.
+#[cfg(feature = "server")]
+fn start_session(username: &str) -> Result<String, String> {
+    use rand_core::RngCore;
+    let mut bytes = [0u8; 32];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+    let session = Session {
+        username: username.to_string(),
+        expires: chrono::Local::now().timestamp() + CONFIG.session_days * 86400,
+    };
+    crate::db::insert_session(&token_key(&token), &session).map_err(|e| e.to_string())?;
+    Ok(token)
+}
+
+#[cfg(feature = "server")]
+fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+/// The logged-in user for a request, if its session cookie is valid. The role
+/// is read from the account each time, so changes apply to existing sessions.
+#[cfg(feature = "server")]
+pub fn session_user(headers: &HeaderMap) -> Option<AuthUser> {
+    let token = session_token(headers)?;
+    let session = crate::db::get_session(&token_key(token)).ok().flatten()?;
+    if session.expires <= chrono::Local::now().timestamp() {
+        return None;
+    }
+    let user = crate::db::get_user(&session.username).ok().flatten()?;
+    Some(AuthUser { username: user.username, role: user.role })
+}
+
+/// Whether a request comes from a user with at least `role`.
+#[cfg(feature = "server")]
+pub fn has_role(headers: &HeaderMap, role: Role) -> bool {
+    session_user(headers).is_some_and(|u| u.role >= role)
+}
+
+/// The user making the current server function call.
+#[cfg(feature = "server")]
+pub async fn current_user() -> Result<AuthUser, ServerFnError> {
+    let headers = dioxus::fullstack::FullstackContext::extract::<HeaderMap, _>().await?;
+    session_user(&headers).ok_or_else(|| ServerFnError::new("Not logged in"))
+}
+
+/// Reject the current server function call unless the user has at least `role`.
+#[cfg(feature = "server")]
+pub async fn require_role(role: Role) -> Result<AuthUser, ServerFnError> {
+    let user = current_user().await?;
+    if user.role < role {
+        return Err(ServerFnError::new(format!("This needs the {} role", role.label())));
+    }
+    Ok(user)
+}
+
+#[cfg(feature = "server")]
+#[derive(Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+    /// Only sent by the first-run setup form.
+    #[serde(default)]
+    confirm: Option<String>,
+    /// Page to return to afterwards.
+    #[serde(default)]
+    next: String,
+}
+
+/// Only allow redirects back into the dashboard.
+#[cfg(feature = "server")]
+fn local_path(next: &str) -> &str {
+    if next.starts_with('/') && !next.starts_with("//") && !next.contains('\\') {
+        next
+    } else {
+        "/"
+    }
+}
+
+#[cfg(feature = "server")]
+fn session_cookie(token: &str) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        SESSION_COOKIE, token, CONFIG.session_days * 86400
+    )
+}
+
+#[cfg(feature = "server")]
+fn logged_in(token: String, next: &str) -> Response {
+    ([(header::SET_COOKIE, session_cookie(&token))], Redirect::to(local_path(next))).into_response()
+}
+
+#[cfg(feature = "server")]
+fn login(form: &LoginForm) -> Result<String, String> {
+    let user = crate::db::get_user(&form.username).map_err(|e| e.to_string())?;
+    match user {
+        Some(user) if verify_password(&form.password, &user.password_hash) => start_session(&user.username),
+        _ => Err("invalid".to_string()),
+    }
+}
+
+/// POST /auth/login
+#[cfg(feature = "server")]
+pub async fn login_handler(axum::Form(form): axum::Form<LoginForm>) -> Response {
+    match tokio::task::spawn_blocking(move || login(&form).map(|token| (token, form.next))).await {
+        Ok(Ok((token, next))) => logged_in(token, &next),
+        Ok(Err(_)) => Redirect::to("/login?error=invalid").into_response(),
+        Err(e) => {
+            println!("Login failed: {}", e);
+            Redirect::to("/login?error=invalid").into_response()
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+fn setup(form: &LoginForm) -> Result<String, String> {
+    let username = form.username.trim();
+    if username.is_empty() {
+        return Err("username".to_string());
+    }
+    if form.password.len() < MIN_PASSWORD_LEN {
+        return Err("short".to_string());
+    }
+    if form.confirm.as_deref() != Some(form.password.as_str()) {
+        return Err("mismatch".to_string());
+    }
+    let user = User {
+        username: username.to_string(),
+        password_hash: hash_password(&form.password)?,
+        created: chrono::Local::now().timestamp(),
+        role: Role::Mentor,
+    };
+    if !crate::db::create_first_user(&user).map_err(|e| e.to_string())? {
+        return Err("exists".to_string());
+    }
+    println!("Created admin user {}", user.username);
+    start_session(&user.username)
+}
+
+/// POST /auth/setup, which only works while there are no users.
+#[cfg(feature = "server")]
+pub async fn setup_handler(axum::Form(form): axum::Form<LoginForm>) -> Response {
+    match tokio::task::spawn_blocking(move || setup(&form).map(|token| (token, form.next))).await {
+        Ok(Ok((token, next))) => logged_in(token, &next),
+        Ok(Err(code)) => Redirect::to(&format!("/login?error={}", code)).into_response(),
+        Err(e) => {
+            println!("Setup failed: {}", e);
+            Redirect::to("/login?error=invalid").into_response()
+        }
+    }
+}
+
+/// POST /auth/logout
+#[cfg(feature = "server")]
+pub async fn logout_handler(headers: HeaderMap) -> Response {
+    if let Some(token) = session_token(&headers)
+        && let Err(e) = crate::db::delete_session(&token_key(token))
+    {
+        println!("Failed to end session: {}", e);
+    }
+    let cookie = format!("{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0", SESSION_COOKIE);
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/")).into_response()
+}
+
+#[server]
+pub async fn get_auth_status() -> Result<AuthStatus, ServerFnError> {
+    if !crate::db::has_users()? {
+        return Ok(AuthStatus::NeedsSetup);
+    }
+    Ok(match current_user().await {
+        Ok(user) => AuthStatus::LoggedIn(user),
+        Err(_) => AuthStatus::LoggedOut,
+    })
+}
+
+/// Fail if `username` is the only mentor, so nobody can lock everyone out of user management.
+#[cfg(feature = "server")]
+fn ensure_other_mentor(username: &str) -> Result<(), ServerFnError> {
+    let others = crate::db::iter_users()?
+        .iter()
+        .any(|u| u.role == Role::Mentor && u.username != username);
+    if !others {
+        return Err(ServerFnError::new("There must be at least one mentor"));
+    }
+    Ok(())
+}
+
+#[server]
+pub async fn list_users() -> Result<Vec<UserInfo>, ServerFnError> {
+    require_role(Role::Mentor).await?;
+    Ok(crate::db::iter_users()?
+        .into_iter()
+        .map(|u| UserInfo { username: u.username, role: u.role, created: u.created })
+        .collect())
+}
+
+#[server]
+pub async fn add_user(username: String, password: String, role: Role) -> Result<(), ServerFnError> {
+    require_role(Role::Mentor).await?;
+    let username = username.trim().to_string();
+    if username.is_empty() {
+        return Err(ServerFnError::new("Username is required"));
+    }
+    if password.len() < MIN_PASSWORD_LEN {
+        return Err(ServerFnError::new(format!("Passwords need at least {} characters", MIN_PASSWORD_LEN)));
+    }
+    if crate::db::get_user(&username)?.is_some() {
+        return Err(ServerFnError::new(format!("{} already exists", username)));
+    }
+    let user = User {
+        password_hash: hash_password(&password).map_err(ServerFnError::new)?,
+        username,
+        created: chrono::Local::now().timestamp(),
+        role,
+    };
+    crate::db::put_user(&user)
+}
+
+#[server]
+pub async fn set_user_role(username: String, role: Role) -> Result<(), ServerFnError> {
+    require_role(Role::Mentor).await?;
+    let mut user = crate::db::get_user(&username)?
+        .ok_or_else(|| ServerFnError::new(format!("No user {}", username)))?;
+    if user.role == Role::Mentor && role != Role::Mentor {
+        ensure_other_mentor(&username)?;
+    }
+    user.role = role;
+    crate::db::put_user(&user)
+}
+
+/// Set a new password and log the user out everywhere.
+#[server]
+pub async fn set_user_password(username: String, password: String) -> Result<(), ServerFnError> {
+    require_role(Role::Mentor).await?;
+    if password.len() < MIN_PASSWORD_LEN {
+        return Err(ServerFnError::new(format!("Passwords need at least {} characters", MIN_PASSWORD_LEN)));
+    }
+    let mut user = crate::db::get_user(&username)?
+        .ok_or_else(|| ServerFnError::new(format!("No user {}", username)))?;
+    user.password_hash = hash_password(&password).map_err(ServerFnError::new)?;
+    crate::db::put_user(&user)?;
+    crate::db::delete_user_sessions(&username)
+}
+
+#[server]
+pub async fn delete_user(username: String) -> Result<(), ServerFnError> {
+    let me = require_role(Role::Mentor).await?;
+    if me.username == username {
+        return Err(ServerFnError::new("You can't delete your own account"));
+    }
+    ensure_other_mentor(&username)?;
+    crate::db::delete_user(&username)?;
+    crate::db::delete_user_sessions(&username)
+}
@@ -1,10 +1,44 @@
-/// Checkin log queries — extracts checkin data from puppet sync records.
+/// Checkin records, parsed from client checkin logs as they arrive.
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
 
+/// A checkin as reported by a client, with an ID that stays the same across resends.
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CheckinEntry {
+    pub id: String,
+    pub log: String,
+}
+
+#[cfg(feature = "server")]
+impl CheckinEntry {
+    /// The ID a client would have given this checkin, for ones sent without IDs.
+    pub fn legacy_id(hostname: &str, log: &str) -> String {
+        use sha2::{Digest, Sha256};
+        let digest = Sha256::digest(format!("{}\n{}", hostname, log));
+        hex::encode(&digest[..16])
+    }
+}
+
+/// A stored checkin. Times are unix seconds.
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CheckinRecord {
+    pub id: String,
+    /// When the student checked in, from the log line (or `received_at` if unparseable).
+    pub timestamp: i64,
+    pub student_id: Option<String>,
+    pub hostname: String,
+    pub received_at: i64,
+    pub log: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CheckinLogEntry {
+    pub id: String,
     pub hostname: String,
+    pub time: String,
+    pub student_id: Option<String>,
     pub log: String,
 }
 
@@ -14,27 +48,70 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 #[cfg(feature = "server")]
 use std::collections::HashMap;
-#[cfg(feature = "server")]
-use std::io::BufReader;
 
 #[cfg(feature = "server")]
 static CODE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(\d{6})\b").unwrap());
 
-/// Load the code-to-name mapping from CSV.
+/// "YYYY-MM-DD HH:MM:SS - 123456", as written by the checkin app.
+#[cfg(feature = "server")]
+static LOG_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}) - (\d{6})\b").unwrap()
+});
+
 #[cfg(feature = "server")]
-fn load_people_map() -> HashMap<String, String> {
-    let file = match std::fs::File::open("/opt/puppet/people.csv") {
-        Ok(f) => f,
-        Err(_) => return HashMap::new(),
+impl CheckinRecord {
+    /// Parse a checkin reported by `hostname`.
+    pub fn parse(entry: &CheckinEntry, hostname: &str, received_at: i64) -> Self {
+        let log = entry.log.trim();
+        let caps = LOG_REGEX.captures(log);
+        let timestamp = caps.as_ref()
+            .and_then(|c| chrono::NaiveDateTime::parse_from_str(&c[1], "%Y-%m-%d %H:%M:%S").ok())
+            .and_then(|t| t.and_local_timezone(chrono::Local).earliest())
+            .map(|t| t.timestamp())
+            .unwrap_or(received_at);
+        let student_id = caps.map(|c| c[2].to_string())
+            .or_else(|| CODE_REGEX.captures(log).map(|c| c[1].to_string()));
+
+        CheckinRecord {
+            id: entry.id.clone(),
+            timestamp,
+            student_id,
+            hostname: hostname.to_string(),
+            received_at,
+            log: log.to_string(),
+        }
+    }
+
+    pub fn to_entry(&self, people: &HashMap<String, String>) -> CheckinLogEntry {
+        let time = chrono::DateTime::from_timestamp(self.timestamp, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%-I:%M %p %-m-%-d-%y").to_string())
+            .unwrap_or_default();
+        CheckinLogEntry {
+            id: self.id.clone(),
+            hostname: self.hostname.clone(),
+            time,
+            student_id: self.student_id.clone(),
+            log: replace_codes(&self.log, people),
+        }
+    }
+}
+
+/// Load the code-to-name mapping from the roster, including former IDs
+/// and alumni so old checkins still resolve.
+#[cfg(feature = "server")]
+pub(crate) fn load_people_map() -> HashMap<String, String> {
+    let people = match crate::db::iter_people() {
+        Ok(people) => people,
+        Err(e) => {
+            println!("Failed to load roster: {}", e);
+            return HashMap::new();
+        }
     };
 
-    let mut reader = csv::Reader::from_reader(BufReader::new(file));
     let mut map = HashMap::new();
-    for record in reader.records().flatten() {
-        if let (Some(code), Some(name)) = (record.get(0), record.get(1)) {
-            if !code.is_empty() && !name.is_empty() {
-                map.insert(code.to_string(), name.to_string());
-            }
+    for person in people {
+        for id in person.former_ids.iter().chain(std::iter::once(&person.id)) {
+            map.insert(id.clone(), person.name.clone());
         }
     }
     map
@@ -42,53 +119,33 @@ fn load_people_map() -> HashMap<String, String> {
 
 /// Replace 6-digit codes with names from the people map.
 #[cfg(feature = "server")]
-fn replace_codes(text: &str, people: &HashMap<String, String>) -> String {
+pub(crate) fn replace_codes(text: &str, people: &HashMap<String, String>) -> String {
     CODE_REGEX.replace_all(text, |caps: &regex::Captures| {
         let code = caps.get(1).unwrap().as_str();
         people.get(code).cloned().unwrap_or_else(|| code.to_string())
     }).to_string()
 }
 
-/// Get all checkin logs across all sync records, sorted newest first.
+/// Most checkins the checkins page is sent at once.
+pub const CHECKIN_PAGE_SIZE: usize = 500;
+
+/// Checkin logs between two dates (YYYY-MM-DD), both inclusive, newest first
+/// and at most `CHECKIN_PAGE_SIZE` of them.
 #[server]
-pub async fn get_all_checkin_logs() -> Result<Vec<CheckinLogEntry>, ServerFnError> {
+pub async fn get_checkin_logs(start: String, end: String) -> Result<Vec<CheckinLogEntry>, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let times = crate::attendance::day_range(&start, &end).map_err(ServerFnError::new)?;
     let people = load_people_map();
-    let all = crate::db::iter_all_syncs()?;
-
-    let mut entries: Vec<(String, CheckinLogEntry)> = Vec::new();
-    for s in all {
-        for log in s.checkin_logs {
-            entries.push((s.timestamp.clone(), CheckinLogEntry {
-                hostname: s.hostname.clone(),
-                log: replace_codes(&log, &people),
-            }));
-        }
-    }
-
-    entries.sort_by(|a, b| b.0.cmp(&a.0));
-    Ok(entries.into_iter().map(|(_, e)| e).collect())
+    Ok(crate::db::checkins_in(times, None, CHECKIN_PAGE_SIZE)?
+        .iter()
+        .map(|c| c.to_entry(&people))
+        .collect())
 }
 
-/// Find a specific checkin log by hostname and processed text.
+/// Find a specific checkin by ID.
 #[server]
-pub async fn get_checkin_log(
-    hostname: String,
-    log_text: String,
-) -> Result<Option<CheckinLogEntry>, ServerFnError> {
+pub async fn get_checkin_log(id: String) -> Result<Option<CheckinLogEntry>, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
     let people = load_people_map();
-    let all = crate::db::iter_all_syncs()?;
-
-    for s in all {
-        if s.hostname != hostname { continue; }
-        for log in &s.checkin_logs {
-            let processed = replace_codes(log, &people);
-            if processed == log_text {
-                return Ok(Some(CheckinLogEntry {
-                    hostname: s.hostname.clone(),
-                    log: processed,
-                }));
-            }
-        }
-    }
-    Ok(None)
+    Ok(crate::db::get_checkin(&id)?.map(|c| c.to_entry(&people)))
 }
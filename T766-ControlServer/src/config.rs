@@ -0,0 +1,139 @@
+/// Server settings read from `/opt/puppet/server.toml`.
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+const CONFIG_PATH: &str = "/opt/puppet/server.toml";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The alert as a JSON object with an `event` tag and a `message`.
+    #[default]
+    Json,
+    /// A Discord webhook message with one embed.
+    Discord,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+}
+
+/// How long sync history is kept.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Retention {
+    /// Days to keep full puppet output.
+    pub full_log_days: i64,
+    /// Days to keep run summaries (status, report, checkins) after the logs are gone.
+    pub summary_days: i64,
+    /// Runs per host kept in full regardless of age, so quiet hosts keep some history.
+    pub min_runs_per_host: usize,
+    /// Days to keep checkins. Unset keeps them forever, since attendance
+    /// records need to outlast puppet history.
+    pub checkin_days: Option<i64>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            full_log_days: 7,
+            summary_days: 90,
+            min_runs_per_host: 20,
+            checkin_days: None,
+        }
+    }
+}
+
+/// How attendance hours are estimated.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Attendance {
+    /// Hours credited for a day with a single checkin, and the least any day counts for.
+    pub min_session_hours: f64,
+}
+
+impl Default for Attendance {
+    fn default() -> Self {
+        Attendance { min_session_hours: 2.0 }
+    }
+}
+
+/// Staged rollouts of new manifest commits, when serving from git.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Rollout {
+    /// Hostname patterns that get a new commit first. Empty sends every
+    /// commit straight to the whole fleet.
+    pub canaries: Vec<String>,
+    /// Successful canary reports before a commit goes to everyone else.
+    pub promote_after: u32,
+}
+
+impl Default for Rollout {
+    fn default() -> Self {
+        Rollout { canaries: Vec::new(), promote_after: 3 }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Missed scheduled syncs before a host is shown as stale.
+    pub stale_after_intervals: u32,
+    /// Missed scheduled syncs before a host is offline and an alert is sent.
+    pub offline_after_intervals: u32,
+    /// Every alert is posted to each of these.
+    pub webhooks: Vec<Webhook>,
+    /// Share of known hosts that must be failing at once to count as a spike.
+    pub spike_fraction: f64,
+    /// Fewest failing hosts that can count as a spike, so two laptops isn't one.
+    pub spike_min_hosts: usize,
+    /// Minimum time between fleet-wide spike alerts.
+    pub alert_cooldown_minutes: i64,
+    pub retention: Retention,
+    pub attendance: Attendance,
+    /// How long a dashboard login lasts.
+    pub session_days: i64,
+    /// Reject clients that don't send a device token. Off until every host
+    /// has been enrolled; a token that is sent is always checked.
+    pub require_device_tokens: bool,
+    /// Local git repository to build bundles from. Unset serves `/puppet` as it is on disk.
+    pub manifest_repo: Option<String>,
+    /// Branch, tag or commit of `manifest_repo` to serve.
+    pub manifest_ref: String,
+    pub rollout: Rollout,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            stale_after_intervals: 2,
+            offline_after_intervals: 6,
+            webhooks: Vec::new(),
+            spike_fraction: 0.25,
+            spike_min_hosts: 3,
+            alert_cooldown_minutes: 60,
+            retention: Retention::default(),
+            attendance: Attendance::default(),
+            session_days: 30,
+            require_device_tokens: false,
+            manifest_repo: None,
+            manifest_ref: "HEAD".to_string(),
+            rollout: Rollout::default(),
+        }
+    }
+}
+
+/// Settings, falling back to defaults if the file is missing or invalid.
+pub static CONFIG: Lazy<ServerConfig> = Lazy::new(|| {
+    match std::fs::read_to_string(CONFIG_PATH) {
+        Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+            println!("Invalid {}, using defaults: {}", CONFIG_PATH, e);
+            ServerConfig::default()
+        }),
+        Err(_) => ServerConfig::default(),
+    }
+});
@@ -1,86 +1,1533 @@
 /// Database access layer for the puppet sync store.
+///
+/// Runs are keyed by `(unix time, hostname)` so time ranges are key ranges,
+/// with a `(hostname, unix time)` index for per-host queries.
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use once_cell::sync::Lazy;
 use redb::{Database, ReadableDatabase, ReadableTable, ReadableTableMetadata, TableDefinition};
 use dioxus::prelude::*;
+use std::io::{Read, Write};
+use std::ops::RangeBounds;
+use crate::alerts::AlertState;
+use crate::auth::{Session, User};
+use crate::checkins::{CheckinEntry, CheckinRecord};
+use crate::config::Retention;
+use crate::devices::Device;
+use crate::groups::Group;
+use crate::hosts::{HostInventory, LastSeen};
+use crate::pause::Pause;
+use crate::people::Person;
+use crate::rollouts::Rollout;
 use crate::sync::PuppetStatus;
 
-pub const SYNC_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("puppet_sync");
+/// Key of a run: when it happened and which host it came from.
+pub type RunKey<'a> = (i64, &'a str);
+
+/// (unix time, hostname) -> PuppetStatus.
+pub const RUNS_TABLE: TableDefinition<RunKey, &[u8]> = TableDefinition::new("runs");
+/// Run key -> gzipped puppet output, split out so it can be pruned sooner.
+pub const LOGS_TABLE: TableDefinition<RunKey, &[u8]> = TableDefinition::new("run_logs");
+/// (hostname, unix time) -> (), the per-host index into RUNS_TABLE.
+pub const HOST_RUNS_TABLE: TableDefinition<(&str, i64), ()> = TableDefinition::new("runs_by_host");
+/// Client report ID -> run key, used to drop replayed reports.
+pub const REPORT_TABLE: TableDefinition<&str, RunKey> = TableDefinition::new("run_report_ids");
+/// Checkin ID -> CheckinRecord.
+pub const CHECKINS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("checkins");
+/// (checkin time, checkin ID) -> hostname, the time index into CHECKINS_TABLE.
+pub const CHECKIN_TIME_TABLE: TableDefinition<(i64, &str), &str> = TableDefinition::new("checkins_by_time");
+/// Hostname -> latest reported HostInventory.
+pub const FACTS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("host_facts");
+/// Hostname -> LastSeen, kept until the host has been silent for `summary_days`.
+pub const LAST_SEEN_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("host_last_seen");
+/// Hostname (or alerts::SPIKE_KEY) -> AlertState.
+pub const ALERT_STATE_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("alert_state");
+/// Student ID -> Person.
+pub const PEOPLE_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("people");
+/// Username -> User.
+pub const USERS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("users");
+/// SHA-256 of a session token -> Session.
+pub const SESSIONS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("sessions");
+/// SHA-256 of a device token -> Device.
+pub const DEVICES_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("devices");
+/// Group name -> Group.
+pub const GROUPS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("host_groups");
+/// Environment name ("" for the default bundle) -> Rollout.
+pub const ROLLOUTS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("rollouts");
+/// Fleet-wide switches, keyed by name.
+pub const FLEET_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("fleet");
+/// FLEET_TABLE key holding the current Pause.
+const PAUSE_KEY: &str = "pause";
+/// One-off migration markers.
+const META_TABLE: TableDefinition<&str, bool> = TableDefinition::new("meta");
+
+// String-keyed tables from before runs were indexed, converted by `migrate`.
+const LEGACY_SYNC_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("puppet_sync");
+const LEGACY_REPORT_TABLE: TableDefinition<&str, &str> = TableDefinition::new("report_ids");
+const LEGACY_CHECKIN_ID_TABLE: TableDefinition<&str, &str> = TableDefinition::new("checkin_ids");
+// Checkin ID -> run key, from when checkin text was kept in runs; removed by `split_checkins`.
+const LEGACY_RUN_CHECKIN_ID_TABLE: TableDefinition<&str, RunKey> = TableDefinition::new("run_checkin_ids");
 
 pub static DB: Lazy<Database> = Lazy::new(|| {
     Database::create("cn-db.redb").expect("Failed to create database")
 });
 
 const MAX_KEY_SIZE: usize = 1_048_576;
-const MAX_KEYS: usize = 500;
-const KEYS_TO_REMOVE: usize = 100;
 
-/// Iterate all PuppetStatus records in the database, newest last.
-pub fn iter_all_syncs() -> Result<Vec<PuppetStatus>, ServerFnError> {
+/// Unix time of a YYYYMMDDHHmmSS local timestamp.
+pub fn timestamp_key(timestamp: &str) -> Option<i64> {
+    chrono::NaiveDateTime::parse_from_str(timestamp, "%Y%m%d%H%M%S")
+        .ok()?
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|t| t.timestamp())
+}
+
+fn compress(text: &str) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(text.as_bytes())?;
+    encoder.finish()
+}
+
+fn decompress(bytes: &[u8]) -> std::io::Result<String> {
+    let mut text = String::new();
+    GzDecoder::new(bytes).read_to_string(&mut text)?;
+    Ok(text)
+}
+
+fn decode_run(value: &[u8]) -> Option<PuppetStatus> {
+    match serde_json::from_slice::<PuppetStatus>(value) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            println!("Failed to deserialize sync data: {}", e);
+            None
+        }
+    }
+}
+
+/// Visit runs newest first until `visit` returns false.
+pub fn visit_runs_rev(mut visit: impl FnMut(PuppetStatus) -> bool) -> Result<(), ServerFnError> {
     let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
-    let table = read_txn.open_table(SYNC_TABLE).map_err(|e| ServerFnError::new(e.to_string()))?;
-    let iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(RUNS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    for item in table.iter().map_err(|e| ServerFnError::new(e.to_string()))?.rev() {
+        let (_, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        if let Some(run) = decode_run(value.value())
+            && !visit(run)
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// A host's runs within a range of unix times, newest first, at most `limit` of them.
+pub fn host_runs(
+    hostname: &str,
+    times: impl RangeBounds<i64>,
+    limit: usize,
+) -> Result<Vec<PuppetStatus>, ServerFnError> {
+    use std::ops::Bound;
+
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let (index, runs) = match (read_txn.open_table(HOST_RUNS_TABLE), read_txn.open_table(RUNS_TABLE)) {
+        (Ok(index), Ok(runs)) => (index, runs),
+        (Err(redb::TableError::TableDoesNotExist(_)), _) | (_, Err(redb::TableError::TableDoesNotExist(_))) => {
+            return Ok(Vec::new());
+        }
+        (Err(e), _) | (_, Err(e)) => return Err(ServerFnError::new(e.to_string())),
+    };
+
+    let start = match times.start_bound() {
+        Bound::Included(t) => Bound::Included((hostname, *t)),
+        Bound::Excluded(t) => Bound::Excluded((hostname, *t)),
+        Bound::Unbounded => Bound::Included((hostname, i64::MIN)),
+    };
+    let end = match times.end_bound() {
+        Bound::Included(t) => Bound::Included((hostname, *t)),
+        Bound::Excluded(t) => Bound::Excluded((hostname, *t)),
+        Bound::Unbounded => Bound::Included((hostname, i64::MAX)),
+    };
 
     let mut results = Vec::new();
-    for item in iter {
-        let (_, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
-        match serde_json::from_slice::<PuppetStatus>(value.value()) {
-            Ok(s) => results.push(s),
-            Err(e) => println!("Failed to deserialize sync data: {}", e),
+    for item in index.range::<(&str, i64)>((start, end)).map_err(|e| ServerFnError::new(e.to_string()))?.rev() {
+        if results.len() >= limit {
+            break;
+        }
+        let (key, _) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        let (_, time) = key.value();
+        if let Some(value) = runs.get((time, hostname)).map_err(|e| ServerFnError::new(e.to_string()))?
+            && let Some(run) = decode_run(value.value())
+        {
+            results.push(run);
         }
     }
     Ok(results)
 }
 
-/// Insert a sync record, evicting oldest entries if storage limit reached.
-pub fn insert_sync(key: &str, json: &str) -> Result<(), ServerFnError> {
-    if json.len() > MAX_KEY_SIZE {
-        return Err(ServerFnError::new(format!(
-            "Data too large: {} bytes exceeds {} byte limit", json.len(), MAX_KEY_SIZE
-        )));
+/// Fill in `logs` on runs read from the runs table, where they've not been pruned.
+pub fn attach_logs(runs: &mut [PuppetStatus]) -> Result<(), ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(LOGS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    for run in runs.iter_mut() {
+        let Some(time) = timestamp_key(&run.timestamp) else { continue };
+        if let Some(value) = table.get((time, run.hostname.as_str())).map_err(|e| ServerFnError::new(e.to_string()))? {
+            run.logs = decompress(value.value()).map_err(|e| ServerFnError::new(e.to_string()))?;
+        }
     }
+    Ok(())
+}
+
+/// Checkins newest first, optionally only those from one host.
+pub fn recent_checkins(hostname: Option<&str>, limit: usize) -> Result<Vec<CheckinRecord>, ServerFnError> {
+    checkins_in(.., hostname, limit)
+}
+
+/// Checkins within a range of unix times, newest first.
+pub fn checkins_in(
+    times: impl RangeBounds<i64>,
+    hostname: Option<&str>,
+    limit: usize,
+) -> Result<Vec<CheckinRecord>, ServerFnError> {
+    use std::ops::Bound;
+
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let (index, checkins) = match (read_txn.open_table(CHECKIN_TIME_TABLE), read_txn.open_table(CHECKINS_TABLE)) {
+        (Ok(index), Ok(checkins)) => (index, checkins),
+        (Err(redb::TableError::TableDoesNotExist(_)), _) | (_, Err(redb::TableError::TableDoesNotExist(_))) => {
+            return Ok(Vec::new());
+        }
+        (Err(e), _) | (_, Err(e)) => return Err(ServerFnError::new(e.to_string())),
+    };
+
+    // IDs sort after "", so (t, "") is the first key at time t.
+    let start = match times.start_bound() {
+        Bound::Included(t) => Bound::Included((*t, "")),
+        Bound::Excluded(t) => match t.checked_add(1) {
+            Some(t) => Bound::Included((t, "")),
+            None => return Ok(Vec::new()),
+        },
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    let end = match times.end_bound() {
+        Bound::Included(t) => match t.checked_add(1) {
+            Some(t) => Bound::Excluded((t, "")),
+            None => Bound::Unbounded,
+        },
+        Bound::Excluded(t) => Bound::Excluded((*t, "")),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+
+    let mut results = Vec::new();
+    for item in index.range::<(i64, &str)>((start, end)).map_err(|e| ServerFnError::new(e.to_string()))?.rev() {
+        if results.len() >= limit {
+            break;
+        }
+        let (key, host) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        if hostname.is_some_and(|h| h != host.value()) {
+            continue;
+        }
+        let (_, id) = key.value();
+        if let Some(value) = checkins.get(id).map_err(|e| ServerFnError::new(e.to_string()))? {
+            match serde_json::from_slice::<CheckinRecord>(value.value()) {
+                Ok(c) => results.push(c),
+                Err(e) => println!("Failed to deserialize checkin: {}", e),
+            }
+        }
+    }
+    Ok(results)
+}
+
+pub fn get_checkin(id: &str) -> Result<Option<CheckinRecord>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(CHECKINS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let value = table.get(id).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(value.and_then(|v| serde_json::from_slice(v.value()).ok()))
+}
+
+/// Store a checkin unless one with the same ID already is. Returns whether it was new.
+fn insert_checkin(write_txn: &redb::WriteTransaction, checkin: &CheckinRecord) -> Result<bool, ServerFnError> {
+    let mut table = write_txn.open_table(CHECKINS_TABLE)
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    if table.get(checkin.id.as_str()).map_err(|e| ServerFnError::new(e.to_string()))?.is_some() {
+        return Ok(false);
+    }
+    let json = serde_json::to_vec(checkin).map_err(|e| ServerFnError::new(e.to_string()))?;
+    table.insert(checkin.id.as_str(), json.as_slice())
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let mut index = write_txn.open_table(CHECKIN_TIME_TABLE)
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    index.insert((checkin.timestamp, checkin.id.as_str()), checkin.hostname.as_str())
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(true)
+}
+
+/// Insert a sync record, with its logs compressed into the logs table and
+/// its checkins parsed into the checkins table.
+///
+/// Returns `false` without storing anything if `report_id` was already seen.
+pub fn insert_sync(
+    mut record: PuppetStatus,
+    report_id: Option<&str>,
+    checkins: &[CheckinEntry],
+) -> Result<bool, ServerFnError> {
+    let time = timestamp_key(&record.timestamp)
+        .ok_or_else(|| ServerFnError::new(format!("Invalid timestamp {}", record.timestamp)))?;
+    let hostname = record.hostname.clone();
+    let key = (time, hostname.as_str());
 
     let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
-    evict_if_full(&write_txn)?;
 
-    {
-        let mut table = write_txn.open_table(SYNC_TABLE)
+    if let Some(report_id) = report_id {
+        let mut reports = write_txn.open_table(REPORT_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let seen = reports.get(report_id)
+            .map_err(|e| ServerFnError::new(e.to_string()))?
+            .is_some();
+        if seen {
+            return Ok(false);
+        }
+        reports.insert(report_id, key)
             .map_err(|e| ServerFnError::new(e.to_string()))?;
-        table.insert(key, json.as_bytes())
+    }
+
+    let received_at = chrono::Local::now().timestamp();
+    for checkin in checkins {
+        insert_checkin(&write_txn, &CheckinRecord::parse(checkin, &hostname, received_at))?;
+    }
+
+    let logs = compress(&std::mem::take(&mut record.logs)).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let json = serde_json::to_string(&record).map_err(|e| ServerFnError::new(e.to_string()))?;
+    if json.len() + logs.len() > MAX_KEY_SIZE {
+        return Err(ServerFnError::new(format!(
+            "Data too large: {} bytes exceeds {} byte limit", json.len() + logs.len(), MAX_KEY_SIZE
+        )));
+    }
+
+    insert_run(&write_txn, key, json.as_bytes())?;
+    {
+        let mut table = write_txn.open_table(LOGS_TABLE)
             .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.insert(key, logs.as_slice()).map_err(|e| ServerFnError::new(e.to_string()))?;
     }
+    update_last_seen(&write_txn, &record)?;
 
     write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(true)
+}
+
+/// Write a run and its host index entry.
+fn insert_run(write_txn: &redb::WriteTransaction, key: RunKey, json: &[u8]) -> Result<(), ServerFnError> {
+    let mut table = write_txn.open_table(RUNS_TABLE)
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    table.insert(key, json).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let mut index = write_txn.open_table(HOST_RUNS_TABLE)
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    index.insert((key.1, key.0), ()).map_err(|e| ServerFnError::new(e.to_string()))?;
     Ok(())
 }
 
-/// Remove oldest entries when the table exceeds MAX_KEYS.
-fn evict_if_full(write_txn: &redb::WriteTransaction) -> Result<(), ServerFnError> {
-    let table = write_txn.open_table(SYNC_TABLE)
+/// Runs of one host that fall outside the retention window, oldest first.
+/// The newest `keep` runs are never included.
+fn expired_host_runs(
+    write_txn: &redb::WriteTransaction,
+    hostname: &str,
+    cutoff: i64,
+    keep: usize,
+) -> Result<Vec<i64>, ServerFnError> {
+    let index = write_txn.open_table(HOST_RUNS_TABLE)
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let mut times = Vec::new();
+    let range = index.range((hostname, i64::MIN)..(hostname, cutoff))
         .map_err(|e| ServerFnError::new(e.to_string()))?;
-    let key_count = table.len().map_err(|e| ServerFnError::new(e.to_string()))?;
+    for item in range {
+        let (key, _) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        times.push(key.value().1);
+    }
 
-    if key_count < MAX_KEYS as u64 {
-        return Ok(());
+    let newer = index.range((hostname, cutoff)..=(hostname, i64::MAX))
+        .map_err(|e| ServerFnError::new(e.to_string()))?
+        .count();
+    let protected = keep.saturating_sub(newer).min(times.len());
+    times.truncate(times.len() - protected);
+    Ok(times)
+}
+
+/// What one pass of `prune` removed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Pruned {
+    pub logs: usize,
+    pub runs: usize,
+    pub checkins: usize,
+    pub hosts: usize,
+}
+
+/// Apply the retention policy: drop logs after `full_log_days`, whole runs
+/// after `summary_days`, but always keep the newest `min_runs_per_host` of
+/// each host intact. Checkins have their own window, `checkin_days`.
+///
+/// Hosts silent for longer than `summary_days` are forgotten: they leave the
+/// host list, the offline checks and the spike count, and lose their facts.
+/// Their kept runs stay readable from the host's page.
+pub fn prune(policy: &Retention) -> Result<Pruned, ServerFnError> {
+    prune_db(&DB, policy)
+}
+
+fn prune_db(db: &Database, policy: &Retention) -> Result<Pruned, ServerFnError> {
+    let now = chrono::Local::now().timestamp();
+    let log_cutoff = now - policy.full_log_days * 86400;
+    let summary_cutoff = now - policy.summary_days * 86400;
+
+    let write_txn = db.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let (hosts, forgotten) = {
+        let table = write_txn.open_table(LAST_SEEN_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut hosts = Vec::new();
+        let mut forgotten = Vec::new();
+        for item in table.iter().map_err(|e| ServerFnError::new(e.to_string()))? {
+            let (key, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+            let silent = serde_json::from_slice::<LastSeen>(value.value()).ok()
+                .and_then(|seen| timestamp_key(&seen.timestamp))
+                .is_some_and(|time| time < summary_cutoff);
+            if silent {
+                forgotten.push(key.value().to_string());
+            }
+            hosts.push(key.value().to_string());
+        }
+        (hosts, forgotten)
+    };
+    let mut logs_removed = 0;
+    let mut checkins_removed = 0;
+    let mut deleted: Vec<(i64, String)> = Vec::new();
+    {
+        let mut logs = write_txn.open_table(LOGS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        for hostname in &hosts {
+            for time in expired_host_runs(&write_txn, hostname, log_cutoff, policy.min_runs_per_host)? {
+                if logs.remove((time, hostname.as_str())).map_err(|e| ServerFnError::new(e.to_string()))?.is_some() {
+                    logs_removed += 1;
+                }
+            }
+            for time in expired_host_runs(&write_txn, hostname, summary_cutoff, policy.min_runs_per_host)? {
+                deleted.push((time, hostname.clone()));
+            }
+        }
+    }
+
+    if !deleted.is_empty() {
+        let mut runs = write_txn.open_table(RUNS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut index = write_txn.open_table(HOST_RUNS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut logs = write_txn.open_table(LOGS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        for (time, hostname) in &deleted {
+            runs.remove((*time, hostname.as_str())).map_err(|e| ServerFnError::new(e.to_string()))?;
+            index.remove((hostname.as_str(), *time)).map_err(|e| ServerFnError::new(e.to_string()))?;
+            logs.remove((*time, hostname.as_str())).map_err(|e| ServerFnError::new(e.to_string()))?;
+        }
+
+        // Forget report IDs whose records are gone; a replay that old is no longer expected.
+        let mut ids = write_txn.open_table(REPORT_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        ids.retain(|_, (time, hostname)| !deleted.iter().any(|(t, h)| *t == time && h == hostname))
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
     }
 
-    let mut keys_to_delete = Vec::new();
-    let mut iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
-    for _ in 0..KEYS_TO_REMOVE.min(key_count as usize) {
-        if let Some(item) = iter.next() {
+    if let Some(days) = policy.checkin_days {
+        let cutoff = now - days * 86400;
+        let mut index = write_txn.open_table(CHECKIN_TIME_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut checkins = write_txn.open_table(CHECKINS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let expired = index.extract_from_if((i64::MIN, "")..(cutoff, ""), |_, _| true)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        for item in expired {
             let (key, _) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
-            keys_to_delete.push(key.value().to_string());
+            checkins.remove(key.value().1).map_err(|e| ServerFnError::new(e.to_string()))?;
+            checkins_removed += 1;
+        }
+    }
+
+    if !forgotten.is_empty() {
+        let mut last_seen = write_txn.open_table(LAST_SEEN_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut facts = write_txn.open_table(FACTS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut alerts = write_txn.open_table(ALERT_STATE_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        for hostname in &forgotten {
+            last_seen.remove(hostname.as_str()).map_err(|e| ServerFnError::new(e.to_string()))?;
+            facts.remove(hostname.as_str()).map_err(|e| ServerFnError::new(e.to_string()))?;
+            alerts.remove(hostname.as_str()).map_err(|e| ServerFnError::new(e.to_string()))?;
         }
     }
-    drop(iter);
-    drop(table);
 
-    let mut table = write_txn.open_table(SYNC_TABLE)
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(Pruned { logs: logs_removed, runs: deleted.len(), checkins: checkins_removed, hosts: forgotten.len() })
+}
+
+/// Prune on startup and then hourly.
+pub async fn prune_task() {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        match tokio::task::spawn_blocking(|| prune(&crate::config::CONFIG.retention)).await {
+            Ok(Ok(pruned)) if pruned == Pruned::default() => {}
+            Ok(Ok(pruned)) => println!(
+                "Retention: removed {} logs, {} runs and {} checkins, forgot {} hosts",
+                pruned.logs, pruned.runs, pruned.checkins, pruned.hosts
+            ),
+            Ok(Err(e)) => println!("Retention pruning failed: {}", e),
+            Err(e) => println!("Retention pruning panicked: {}", e),
+        }
+        if let Err(e) = prune_sessions() {
+            println!("Failed to remove expired sessions: {}", e);
+        }
+    }
+}
+
+/// Record a report in the last-seen table unless a newer one is already there.
+fn update_last_seen(write_txn: &redb::WriteTransaction, record: &PuppetStatus) -> Result<(), ServerFnError> {
+    let mut table = write_txn.open_table(LAST_SEEN_TABLE)
         .map_err(|e| ServerFnError::new(e.to_string()))?;
-    for key in &keys_to_delete {
-        table.remove(key.as_str()).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let newer_stored = table.get(record.hostname.as_str())
+        .map_err(|e| ServerFnError::new(e.to_string()))?
+        .and_then(|v| serde_json::from_slice::<LastSeen>(v.value()).ok())
+        .is_some_and(|seen| seen.timestamp >= record.timestamp);
+    if newer_stored {
+        return Ok(());
     }
 
-    println!("Storage limit reached ({} keys), removed {} oldest entries",
-             key_count, keys_to_delete.len());
+    let seen = LastSeen {
+        timestamp: record.timestamp.clone(),
+        schedule: record.schedule.clone(),
+        commit: record.commit.clone(),
+        alerted: false,
+    };
+    let json = serde_json::to_vec(&seen).map_err(|e| ServerFnError::new(e.to_string()))?;
+    table.insert(record.hostname.as_str(), json.as_slice())
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
     Ok(())
 }
+
+/// Bring an older database up to the current schema. Runs at startup.
+pub fn migrate() -> Result<(), ServerFnError> {
+    migrate_legacy_syncs(&DB)?;
+    split_logs(&DB)?;
+    split_checkins(&DB)
+}
+
+/// Convert the string-keyed sync table into the indexed runs tables.
+/// Does nothing if there's no legacy table left.
+fn migrate_legacy_syncs(db: &Database) -> Result<(), ServerFnError> {
+    let write_txn = db.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let mut records = Vec::new();
+    {
+        let legacy = write_txn.open_table(LEGACY_SYNC_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        if legacy.is_empty().map_err(|e| ServerFnError::new(e.to_string()))? {
+            drop(legacy);
+            write_txn.delete_table(LEGACY_SYNC_TABLE).map_err(|e| ServerFnError::new(e.to_string()))?;
+            write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+            return Ok(());
+        }
+        for item in legacy.iter().map_err(|e| ServerFnError::new(e.to_string()))? {
+            let (_, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+            if let Some(record) = decode_run(value.value()) {
+                records.push(record);
+            }
+        }
+    }
+
+    let mut migrated = 0;
+    for record in &records {
+        let Some(time) = timestamp_key(&record.timestamp) else {
+            println!("Skipping sync with invalid timestamp {}", record.timestamp);
+            continue;
+        };
+        let key = (time, record.hostname.as_str());
+        let json = serde_json::to_vec(record).map_err(|e| ServerFnError::new(e.to_string()))?;
+        insert_run(&write_txn, key, &json)?;
+        update_last_seen(&write_txn, record)?;
+        migrated += 1;
+    }
+
+    // Report IDs pointed at string keys; map them onto the new ones.
+    {
+        let legacy = write_txn.open_table(LEGACY_REPORT_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut reports = write_txn.open_table(REPORT_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        for item in legacy.iter().map_err(|e| ServerFnError::new(e.to_string()))? {
+            let (id, old_key) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+            // "sync:<timestamp>:<hostname>"
+            let mut parts = old_key.value().splitn(3, ':').skip(1);
+            if let (Some(timestamp), Some(hostname)) = (parts.next(), parts.next())
+                && let Some(time) = timestamp_key(timestamp)
+            {
+                reports.insert(id.value(), (time, hostname))
+                    .map_err(|e| ServerFnError::new(e.to_string()))?;
+            }
+        }
+    }
+
+    for definition in [LEGACY_REPORT_TABLE, LEGACY_CHECKIN_ID_TABLE] {
+        write_txn.delete_table(definition).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.delete_table(LEGACY_SYNC_TABLE).map_err(|e| ServerFnError::new(e.to_string()))?;
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    println!("Migrated {} syncs to indexed tables", migrated);
+    Ok(())
+}
+
+/// Move logs stored inline in runs into the compressed logs table.
+fn split_logs(db: &Database) -> Result<(), ServerFnError> {
+    const MARKER: &str = "logs_split";
+
+    let write_txn = db.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut meta = write_txn.open_table(META_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        if meta.get(MARKER).map_err(|e| ServerFnError::new(e.to_string()))?.is_some() {
+            return Ok(());
+        }
+        meta.insert(MARKER, true).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+
+    let mut moved = 0;
+    {
+        let mut runs = write_txn.open_table(RUNS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut logs = write_txn.open_table(LOGS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+        let mut with_logs = Vec::new();
+        for item in runs.iter().map_err(|e| ServerFnError::new(e.to_string()))? {
+            let (key, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+            let (time, hostname) = key.value();
+            if let Some(run) = decode_run(value.value()).filter(|r| !r.logs.is_empty()) {
+                with_logs.push((time, hostname.to_string(), run));
+            }
+        }
+
+        for (time, hostname, mut run) in with_logs {
+            let compressed = compress(&std::mem::take(&mut run.logs)).map_err(|e| ServerFnError::new(e.to_string()))?;
+            let json = serde_json::to_vec(&run).map_err(|e| ServerFnError::new(e.to_string()))?;
+            logs.insert((time, hostname.as_str()), compressed.as_slice())
+                .map_err(|e| ServerFnError::new(e.to_string()))?;
+            runs.insert((time, hostname.as_str()), json.as_slice())
+                .map_err(|e| ServerFnError::new(e.to_string()))?;
+            moved += 1;
+        }
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    println!("Compressed logs of {} runs", moved);
+    Ok(())
+}
+
+/// Move checkins stored as text in runs into the checkins table.
+fn split_checkins(db: &Database) -> Result<(), ServerFnError> {
+    const MARKER: &str = "checkins_split";
+
+    let write_txn = db.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut meta = write_txn.open_table(META_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        if meta.get(MARKER).map_err(|e| ServerFnError::new(e.to_string()))?.is_some() {
+            return Ok(());
+        }
+        meta.insert(MARKER, true).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+
+    let mut with_checkins = Vec::new();
+    {
+        let runs = write_txn.open_table(RUNS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        for item in runs.iter().map_err(|e| ServerFnError::new(e.to_string()))? {
+            let (key, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+            let (time, _) = key.value();
+            if let Some(run) = decode_run(value.value()).filter(|r| !r.checkin_logs.is_empty()) {
+                with_checkins.push((time, run));
+            }
+        }
+    }
+
+    let mut moved = 0;
+    for (time, mut run) in with_checkins {
+        for log in std::mem::take(&mut run.checkin_logs) {
+            let entry = CheckinEntry { id: CheckinEntry::legacy_id(&run.hostname, &log), log };
+            if insert_checkin(&write_txn, &CheckinRecord::parse(&entry, &run.hostname, time))? {
+                moved += 1;
+            }
+        }
+        let json = serde_json::to_vec(&run).map_err(|e| ServerFnError::new(e.to_string()))?;
+        let mut runs = write_txn.open_table(RUNS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        runs.insert((time, run.hostname.as_str()), json.as_slice())
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+
+    write_txn.delete_table(LEGACY_RUN_CHECKIN_ID_TABLE).map_err(|e| ServerFnError::new(e.to_string()))?;
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    println!("Moved {} checkins into the checkins table", moved);
+    Ok(())
+}
+
+/// Every host that has ever reported, with when it last did.
+pub fn iter_last_seen() -> Result<Vec<(String, LastSeen)>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(LAST_SEEN_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for item in iter {
+        let (key, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        match serde_json::from_slice::<LastSeen>(value.value()) {
+            Ok(seen) => results.push((key.value().to_string(), seen)),
+            Err(e) => println!("Failed to deserialize last seen: {}", e),
+        }
+    }
+    Ok(results)
+}
+
+/// Note that a silent-host alert went out, unless the host reported in the meantime.
+pub fn mark_alerted(hostname: &str, timestamp: &str) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(LAST_SEEN_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let seen = table.get(hostname)
+            .map_err(|e| ServerFnError::new(e.to_string()))?
+            .and_then(|v| serde_json::from_slice::<LastSeen>(v.value()).ok());
+        if let Some(mut seen) = seen.filter(|s| s.timestamp == timestamp) {
+            seen.alerted = true;
+            let json = serde_json::to_vec(&seen).map_err(|e| ServerFnError::new(e.to_string()))?;
+            table.insert(hostname, json.as_slice())
+                .map_err(|e| ServerFnError::new(e.to_string()))?;
+        }
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Store a host's facts unless newer ones are already stored.
+pub fn upsert_facts(inventory: &HostInventory) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(FACTS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let newer_stored = table.get(inventory.hostname.as_str())
+            .map_err(|e| ServerFnError::new(e.to_string()))?
+            .and_then(|v| serde_json::from_slice::<HostInventory>(v.value()).ok())
+            .is_some_and(|stored| stored.timestamp > inventory.timestamp);
+        if !newer_stored {
+            let json = serde_json::to_vec(inventory).map_err(|e| ServerFnError::new(e.to_string()))?;
+            table.insert(inventory.hostname.as_str(), json.as_slice())
+                .map_err(|e| ServerFnError::new(e.to_string()))?;
+        }
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Latest facts for every host that has reported any.
+pub fn iter_facts() -> Result<Vec<HostInventory>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(FACTS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for item in iter {
+        let (_, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        match serde_json::from_slice::<HostInventory>(value.value()) {
+            Ok(h) => results.push(h),
+            Err(e) => println!("Failed to deserialize host facts: {}", e),
+        }
+    }
+    Ok(results)
+}
+
+/// Latest facts for one host.
+pub fn get_facts(hostname: &str) -> Result<Option<HostInventory>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(FACTS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let value = table.get(hostname).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(value.and_then(|v| serde_json::from_slice(v.value()).ok()))
+}
+
+pub fn get_alert_state(key: &str) -> Result<Option<AlertState>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(ALERT_STATE_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let value = table.get(key).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(value.and_then(|v| serde_json::from_slice(v.value()).ok()))
+}
+
+/// Read, change and store an alert state in one write transaction, so two
+/// reports can't both act on the same previous state. Returns the state from
+/// before the change, or `None` if `update` left it alone.
+pub fn update_alert_state(key: &str, update: impl FnOnce(&mut AlertState)) -> Result<Option<AlertState>, ServerFnError> {
+    update_alert_state_db(&DB, key, update)
+}
+
+fn update_alert_state_db(db: &Database, key: &str, update: impl FnOnce(&mut AlertState)) -> Result<Option<AlertState>, ServerFnError> {
+    let write_txn = db.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let previous = {
+        let mut table = write_txn.open_table(ALERT_STATE_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let previous: AlertState = table.get(key)
+            .map_err(|e| ServerFnError::new(e.to_string()))?
+            .and_then(|v| serde_json::from_slice(v.value()).ok())
+            .unwrap_or_default();
+        let mut state = previous.clone();
+        update(&mut state);
+        if state == previous {
+            return Ok(None);
+        }
+        let json = serde_json::to_vec(&state).map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.insert(key, json.as_slice()).map_err(|e| ServerFnError::new(e.to_string()))?;
+        previous
+    };
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(Some(previous))
+}
+
+/// The whole roster, in ID order.
+pub fn iter_people() -> Result<Vec<Person>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(PEOPLE_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for item in iter {
+        let (_, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        match serde_json::from_slice::<Person>(value.value()) {
+            Ok(p) => results.push(p),
+            Err(e) => println!("Failed to deserialize person: {}", e),
+        }
+    }
+    Ok(results)
+}
+
+pub fn get_person(id: &str) -> Result<Option<Person>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(PEOPLE_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let value = table.get(id).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(value.and_then(|v| serde_json::from_slice(v.value()).ok()))
+}
+
+/// Insert or overwrite people by ID in one transaction.
+pub fn put_people(people: &[Person]) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(PEOPLE_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        for person in people {
+            let json = serde_json::to_vec(person).map_err(|e| ServerFnError::new(e.to_string()))?;
+            table.insert(person.id.as_str(), json.as_slice())
+                .map_err(|e| ServerFnError::new(e.to_string()))?;
+        }
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Store a person under a new ID, removing the old entry.
+pub fn replace_person(old_id: &str, person: &Person) -> Result<(), ServerFnError> {
+    let json = serde_json::to_vec(person).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(PEOPLE_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.remove(old_id).map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.insert(person.id.as_str(), json.as_slice())
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+pub fn has_users() -> Result<bool, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    match read_txn.open_table(USERS_TABLE) {
+        Ok(t) => Ok(!t.is_empty().map_err(|e| ServerFnError::new(e.to_string()))?),
+        Err(redb::TableError::TableDoesNotExist(_)) => Ok(false),
+        Err(e) => Err(ServerFnError::new(e.to_string())),
+    }
+}
+
+pub fn get_user(username: &str) -> Result<Option<User>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(USERS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let value = table.get(username).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(value.and_then(|v| serde_json::from_slice(v.value()).ok()))
+}
+
+/// Store the first user. Returns `false`, storing nothing, if any user already exists.
+pub fn create_first_user(user: &User) -> Result<bool, ServerFnError> {
+    let json = serde_json::to_vec(user).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(USERS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        if !table.is_empty().map_err(|e| ServerFnError::new(e.to_string()))? {
+            return Ok(false);
+        }
+        table.insert(user.username.as_str(), json.as_slice())
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(true)
+}
+
+pub fn iter_users() -> Result<Vec<User>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(USERS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for item in iter {
+        let (_, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        match serde_json::from_slice::<User>(value.value()) {
+            Ok(u) => results.push(u),
+            Err(e) => println!("Failed to deserialize user: {}", e),
+        }
+    }
+    Ok(results)
+}
+
+pub fn put_user(user: &User) -> Result<(), ServerFnError> {
+    let json = serde_json::to_vec(user).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(USERS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.insert(user.username.as_str(), json.as_slice())
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+pub fn delete_user(username: &str) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(USERS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.remove(username).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+pub fn insert_session(key: &str, session: &Session) -> Result<(), ServerFnError> {
+    let json = serde_json::to_vec(session).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(SESSIONS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.insert(key, json.as_slice()).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+pub fn get_session(key: &str) -> Result<Option<Session>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(SESSIONS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let value = table.get(key).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(value.and_then(|v| serde_json::from_slice(v.value()).ok()))
+}
+
+pub fn delete_session(key: &str) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(SESSIONS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.remove(key).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// End every session belonging to `username`.
+pub fn delete_user_sessions(username: &str) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(SESSIONS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.retain(|_, value| {
+            serde_json::from_slice::<Session>(value).is_ok_and(|s| s.username != username)
+        }).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Drop sessions past their expiry.
+pub fn prune_sessions() -> Result<(), ServerFnError> {
+    let now = chrono::Local::now().timestamp();
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(SESSIONS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.retain(|_, value| {
+            serde_json::from_slice::<Session>(value).is_ok_and(|s| s.expires > now)
+        }).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+pub fn get_device(key: &str) -> Result<Option<Device>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(DEVICES_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let value = table.get(key).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(value.and_then(|v| serde_json::from_slice(v.value()).ok()))
+}
+
+pub fn iter_devices() -> Result<Vec<Device>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(DEVICES_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for item in iter {
+        let (_, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        match serde_json::from_slice::<Device>(value.value()) {
+            Ok(d) => results.push(d),
+            Err(e) => println!("Failed to deserialize device: {}", e),
+        }
+    }
+    Ok(results)
+}
+
+/// Store a device's token, dropping any token it had before.
+pub fn replace_device(key: &str, device: &Device) -> Result<(), ServerFnError> {
+    let json = serde_json::to_vec(device).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(DEVICES_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.retain(|_, value| {
+            serde_json::from_slice::<Device>(value).is_ok_and(|d| d.hostname != device.hostname)
+        }).map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.insert(key, json.as_slice()).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+pub fn revoke_device(hostname: &str) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(DEVICES_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.retain(|_, value| {
+            serde_json::from_slice::<Device>(value).is_ok_and(|d| d.hostname != hostname)
+        }).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Every group, in name order.
+pub fn iter_groups() -> Result<Vec<Group>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(GROUPS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for item in iter {
+        let (_, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        match serde_json::from_slice::<Group>(value.value()) {
+            Ok(g) => results.push(g),
+            Err(e) => println!("Failed to deserialize group: {}", e),
+        }
+    }
+    Ok(results)
+}
+
+/// Insert or overwrite groups by name in one transaction.
+pub fn put_groups(groups: &[Group]) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(GROUPS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        for group in groups {
+            let json = serde_json::to_vec(group).map_err(|e| ServerFnError::new(e.to_string()))?;
+            table.insert(group.name.as_str(), json.as_slice())
+                .map_err(|e| ServerFnError::new(e.to_string()))?;
+        }
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+pub fn delete_group(name: &str) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(GROUPS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.remove(name).map_err(|e| ServerFnError::new(e.to_string()))?;
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Every environment's rollout, in environment order.
+pub fn iter_rollouts() -> Result<Vec<(String, Rollout)>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(ROLLOUTS_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let iter = table.iter().map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for item in iter {
+        let (key, value) = item.map_err(|e| ServerFnError::new(e.to_string()))?;
+        match serde_json::from_slice::<Rollout>(value.value()) {
+            Ok(r) => results.push((key.value().to_string(), r)),
+            Err(e) => println!("Failed to deserialize rollout: {}", e),
+        }
+    }
+    Ok(results)
+}
+
+/// Read, change and store a rollout in one transaction, so reports and
+/// bundle requests arriving together can't undo each other. Returns the
+/// rollout as stored.
+pub fn update_rollout(key: &str, update: impl FnOnce(&mut Rollout)) -> Result<Rollout, ServerFnError> {
+    update_rollout_db(&DB, key, update)
+}
+
+fn update_rollout_db(db: &Database, key: &str, update: impl FnOnce(&mut Rollout)) -> Result<Rollout, ServerFnError> {
+    let write_txn = db.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let rollout = {
+        let mut table = write_txn.open_table(ROLLOUTS_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        let previous: Rollout = table.get(key)
+            .map_err(|e| ServerFnError::new(e.to_string()))?
+            .and_then(|v| serde_json::from_slice(v.value()).ok())
+            .unwrap_or_default();
+        let mut rollout = previous.clone();
+        update(&mut rollout);
+        if rollout == previous {
+            return Ok(rollout);
+        }
+        let json = serde_json::to_vec(&rollout).map_err(|e| ServerFnError::new(e.to_string()))?;
+        table.insert(key, json.as_slice()).map_err(|e| ServerFnError::new(e.to_string()))?;
+        rollout
+    };
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(rollout)
+}
+
+pub fn get_pause() -> Result<Option<Pause>, ServerFnError> {
+    let read_txn = DB.begin_read().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let table = match read_txn.open_table(FLEET_TABLE) {
+        Ok(t) => t,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    let value = table.get(PAUSE_KEY).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(value.and_then(|v| serde_json::from_slice(v.value()).ok()))
+}
+
+/// Turn the pause on, replacing any in effect, or off with `None`.
+pub fn set_pause(pause: Option<&Pause>) -> Result<(), ServerFnError> {
+    let write_txn = DB.begin_write().map_err(|e| ServerFnError::new(e.to_string()))?;
+    {
+        let mut table = write_txn.open_table(FLEET_TABLE)
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        match pause {
+            Some(pause) => {
+                let json = serde_json::to_vec(pause).map_err(|e| ServerFnError::new(e.to_string()))?;
+                table.insert(PAUSE_KEY, json.as_slice()).map_err(|e| ServerFnError::new(e.to_string()))?;
+            }
+            None => {
+                table.remove(PAUSE_KEY).map_err(|e| ServerFnError::new(e.to_string()))?;
+            }
+        }
+    }
+    write_txn.commit().map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh database, removed with the returned directory.
+    fn temp_db() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.redb")).unwrap();
+        (dir, db)
+    }
+
+    fn run(hostname: &str, timestamp: &str) -> PuppetStatus {
+        PuppetStatus {
+            hostname: hostname.to_string(),
+            status: "success".to_string(),
+            exit_code: 0,
+            timestamp: timestamp.to_string(),
+            logs: String::new(),
+            checkin_logs: Vec::new(),
+            schedule: None,
+            report: None,
+            commit: None,
+            offline: false,
+        }
+    }
+
+    /// Store `record` as it is, without splitting anything out of it.
+    fn store_run(db: &Database, record: &PuppetStatus) {
+        let write_txn = db.begin_write().unwrap();
+        let key = (timestamp_key(&record.timestamp).unwrap(), record.hostname.as_str());
+        insert_run(&write_txn, key, &serde_json::to_vec(record).unwrap()).unwrap();
+        update_last_seen(&write_txn, record).unwrap();
+        write_txn.commit().unwrap();
+    }
+
+    fn get_run(db: &Database, key: RunKey) -> Option<PuppetStatus> {
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(RUNS_TABLE).unwrap();
+        table.get(key).unwrap().map(|v| serde_json::from_slice(v.value()).unwrap())
+    }
+
+    fn table_exists<K: redb::Key + 'static, V: redb::Value + 'static>(db: &Database, definition: TableDefinition<K, V>) -> bool {
+        !matches!(db.begin_read().unwrap().open_table(definition), Err(redb::TableError::TableDoesNotExist(_)))
+    }
+
+    #[test]
+    fn migrate_legacy_syncs_moves_runs_and_report_ids() {
+        let (_dir, db) = temp_db();
+        let mut legacy_run = run("LAB-1", "20240105093000");
+        legacy_run.logs = "Notice: Applied catalog".to_string();
+        {
+            let write_txn = db.begin_write().unwrap();
+            let mut legacy = write_txn.open_table(LEGACY_SYNC_TABLE).unwrap();
+            legacy.insert("sync:20240105093000:LAB-1", serde_json::to_vec(&legacy_run).unwrap().as_slice()).unwrap();
+            legacy.insert("sync:bad:LAB-2", serde_json::to_vec(&run("LAB-2", "bad")).unwrap().as_slice()).unwrap();
+            let mut reports = write_txn.open_table(LEGACY_REPORT_TABLE).unwrap();
+            reports.insert("report-1", "sync:20240105093000:LAB-1").unwrap();
+            drop((legacy, reports));
+            write_txn.commit().unwrap();
+        }
+
+        migrate_legacy_syncs(&db).unwrap();
+
+        let time = timestamp_key("20240105093000").unwrap();
+        assert_eq!(get_run(&db, (time, "LAB-1")), Some(legacy_run));
+        let read_txn = db.begin_read().unwrap();
+        assert!(read_txn.open_table(HOST_RUNS_TABLE).unwrap().get(("LAB-1", time)).unwrap().is_some());
+        let reports = read_txn.open_table(REPORT_TABLE).unwrap();
+        assert_eq!(reports.get("report-1").unwrap().map(|v| v.value().0), Some(time));
+        let last_seen = read_txn.open_table(LAST_SEEN_TABLE).unwrap();
+        assert!(last_seen.get("LAB-1").unwrap().is_some());
+        // Records with unreadable timestamps are dropped rather than blocking the migration.
+        assert!(last_seen.get("LAB-2").unwrap().is_none());
+        drop((reports, last_seen, read_txn));
+
+        assert!(!table_exists(&db, LEGACY_SYNC_TABLE));
+        assert!(!table_exists(&db, LEGACY_REPORT_TABLE));
+    }
+
+    #[test]
+    fn migrate_legacy_syncs_without_legacy_table_does_nothing() {
+        let (_dir, db) = temp_db();
+        migrate_legacy_syncs(&db).unwrap();
+        assert!(!table_exists(&db, LEGACY_SYNC_TABLE));
+        assert!(!table_exists(&db, RUNS_TABLE));
+    }
+
+    #[test]
+    fn split_logs_compresses_inline_logs_once() {
+        let (_dir, db) = temp_db();
+        let mut record = run("LAB-1", "20240105093000");
+        record.logs = "Notice: Applied catalog".to_string();
+        store_run(&db, &record);
+
+        split_logs(&db).unwrap();
+
+        let key = (timestamp_key(&record.timestamp).unwrap(), "LAB-1");
+        assert_eq!(get_run(&db, key).unwrap().logs, "");
+        {
+            let read_txn = db.begin_read().unwrap();
+            let logs = read_txn.open_table(LOGS_TABLE).unwrap();
+            assert_eq!(decompress(logs.get(key).unwrap().unwrap().value()).unwrap(), "Notice: Applied catalog");
+        }
+
+        // The marker stops it from running again on later startups.
+        let mut later = run("LAB-1", "20240105100000");
+        later.logs = "inline".to_string();
+        store_run(&db, &later);
+        split_logs(&db).unwrap();
+        assert_eq!(get_run(&db, (timestamp_key(&later.timestamp).unwrap(), "LAB-1")).unwrap().logs, "inline");
+    }
+
+    #[test]
+    fn split_checkins_moves_checkin_text_into_checkins_table() {
+        let (_dir, db) = temp_db();
+        let log = "2024-01-05 09:15:00 - 123456".to_string();
+        let mut record = run("LAB-1", "20240105093000");
+        // The same checkin sent twice is only stored once.
+        record.checkin_logs = vec![log.clone(), log.clone()];
+        store_run(&db, &record);
+
+        split_checkins(&db).unwrap();
+
+        let key = (timestamp_key(&record.timestamp).unwrap(), "LAB-1");
+        assert!(get_run(&db, key).unwrap().checkin_logs.is_empty());
+
+        let id = CheckinEntry::legacy_id("LAB-1", &log);
+        let read_txn = db.begin_read().unwrap();
+        let checkins = read_txn.open_table(CHECKINS_TABLE).unwrap();
+        assert_eq!(checkins.len().unwrap(), 1);
+        let checkin: CheckinRecord = serde_json::from_slice(checkins.get(id.as_str()).unwrap().unwrap().value()).unwrap();
+        assert_eq!(checkin.student_id.as_deref(), Some("123456"));
+        assert_eq!(checkin.hostname, "LAB-1");
+        assert_eq!(checkin.received_at, key.0);
+        let index = read_txn.open_table(CHECKIN_TIME_TABLE).unwrap();
+        assert_eq!(index.get((checkin.timestamp, id.as_str())).unwrap().map(|v| v.value().to_string()), Some("LAB-1".to_string()));
+    }
+
+    /// Run timestamp from `days` ago.
+    fn days_ago(days: i64) -> String {
+        (chrono::Local::now() - chrono::Duration::days(days)).format("%Y%m%d%H%M%S").to_string()
+    }
+
+    /// Checkin log time from `days` ago, as the checkin app writes it.
+    fn days_ago_log(days: i64) -> String {
+        (chrono::Local::now() - chrono::Duration::days(days)).format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// Store a run the way `insert_sync` does, with its logs split out.
+    fn store_with_logs(db: &Database, hostname: &'static str, timestamp: &str) -> RunKey<'static> {
+        let record = run(hostname, timestamp);
+        store_run(db, &record);
+        let time = timestamp_key(timestamp).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut logs = write_txn.open_table(LOGS_TABLE).unwrap();
+            logs.insert((time, hostname), compress("output").unwrap().as_slice()).unwrap();
+        }
+        write_txn.commit().unwrap();
+        (time, hostname)
+    }
+
+    fn has_logs(db: &Database, key: RunKey) -> bool {
+        let read_txn = db.begin_read().unwrap();
+        read_txn.open_table(LOGS_TABLE).unwrap().get(key).unwrap().is_some()
+    }
+
+    fn policy(min_runs_per_host: usize) -> Retention {
+        Retention { full_log_days: 7, summary_days: 90, min_runs_per_host, checkin_days: None }
+    }
+
+    #[test]
+    fn prune_drops_logs_then_runs_by_age() {
+        let (_dir, db) = temp_db();
+        let fresh = store_with_logs(&db, "LAB-1", &days_ago(1));
+        let week_old = store_with_logs(&db, "LAB-1", &days_ago(10));
+        let ancient = store_with_logs(&db, "LAB-1", &days_ago(100));
+
+        let pruned = prune_db(&db, &policy(0)).unwrap();
+
+        assert_eq!(pruned, Pruned { logs: 2, runs: 1, checkins: 0, hosts: 0 });
+        assert!(has_logs(&db, fresh));
+        assert!(get_run(&db, week_old).is_some());
+        assert!(!has_logs(&db, week_old));
+        assert!(get_run(&db, ancient).is_none());
+        assert!(!has_logs(&db, ancient));
+        let read_txn = db.begin_read().unwrap();
+        assert!(read_txn.open_table(HOST_RUNS_TABLE).unwrap().get(("LAB-1", ancient.0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn prune_keeps_the_newest_runs_of_each_host() {
+        let (_dir, db) = temp_db();
+        // A host that went quiet months ago keeps its last two runs in full.
+        let quiet: Vec<_> = [200, 150, 120].iter().map(|d| store_with_logs(&db, "LAB-1", &days_ago(*d))).collect();
+        // One recent run only protects a single older one.
+        let recent = store_with_logs(&db, "LAB-2", &days_ago(1));
+        let old = store_with_logs(&db, "LAB-2", &days_ago(100));
+        let older = store_with_logs(&db, "LAB-2", &days_ago(110));
+
+        let pruned = prune_db(&db, &policy(2)).unwrap();
+
+        assert_eq!(pruned.runs, 2);
+        assert!(get_run(&db, quiet[0]).is_none());
+        assert!(has_logs(&db, quiet[1]) && has_logs(&db, quiet[2]));
+        assert!(has_logs(&db, recent) && has_logs(&db, old));
+        assert!(get_run(&db, older).is_none());
+    }
+
+    #[test]
+    fn prune_forgets_hosts_silent_past_the_summary_window() {
+        let (_dir, db) = temp_db();
+        let kept = store_with_logs(&db, "LAB-1", &days_ago(120));
+        store_with_logs(&db, "LAB-2", &days_ago(1));
+        {
+            let write_txn = db.begin_write().unwrap();
+            let mut alerts = write_txn.open_table(ALERT_STATE_TABLE).unwrap();
+            alerts.insert("LAB-1", serde_json::to_vec(&AlertState::default()).unwrap().as_slice()).unwrap();
+            drop(alerts);
+            write_txn.commit().unwrap();
+        }
+
+        let pruned = prune_db(&db, &policy(1)).unwrap();
+
+        assert_eq!(pruned.hosts, 1);
+        let read_txn = db.begin_read().unwrap();
+        let last_seen = read_txn.open_table(LAST_SEEN_TABLE).unwrap();
+        assert!(last_seen.get("LAB-1").unwrap().is_none());
+        assert!(last_seen.get("LAB-2").unwrap().is_some());
+        assert!(read_txn.open_table(ALERT_STATE_TABLE).unwrap().get("LAB-1").unwrap().is_none());
+        assert!(get_run(&db, kept).is_some());
+    }
+
+    #[test]
+    fn prune_forgets_report_ids_of_deleted_runs() {
+        let (_dir, db) = temp_db();
+        let kept = store_with_logs(&db, "LAB-1", &days_ago(1));
+        let deleted = store_with_logs(&db, "LAB-1", &days_ago(100));
+        {
+            let write_txn = db.begin_write().unwrap();
+            let mut reports = write_txn.open_table(REPORT_TABLE).unwrap();
+            reports.insert("kept", kept).unwrap();
+            reports.insert("deleted", deleted).unwrap();
+            drop(reports);
+            write_txn.commit().unwrap();
+        }
+
+        prune_db(&db, &policy(0)).unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let reports = read_txn.open_table(REPORT_TABLE).unwrap();
+        assert!(reports.get("kept").unwrap().is_some());
+        assert!(reports.get("deleted").unwrap().is_none());
+    }
+
+    #[test]
+    fn prune_keeps_checkins_unless_checkin_days_is_set() {
+        let (_dir, db) = temp_db();
+        let received_at = chrono::Local::now().timestamp();
+        let old = CheckinEntry { id: "old".to_string(), log: format!("{} - 123456", days_ago_log(800)) };
+        let new = CheckinEntry { id: "new".to_string(), log: format!("{} - 123456", days_ago_log(10)) };
+        {
+            let write_txn = db.begin_write().unwrap();
+            for entry in [&old, &new] {
+                insert_checkin(&write_txn, &CheckinRecord::parse(entry, "LAB-1", received_at)).unwrap();
+            }
+            write_txn.commit().unwrap();
+        }
+
+        assert_eq!(prune_db(&db, &policy(0)).unwrap().checkins, 0);
+        let pruned = prune_db(&db, &Retention { checkin_days: Some(730), ..policy(0) }).unwrap();
+
+        assert_eq!(pruned.checkins, 1);
+        let read_txn = db.begin_read().unwrap();
+        let checkins = read_txn.open_table(CHECKINS_TABLE).unwrap();
+        assert!(checkins.get("old").unwrap().is_none());
+        assert!(checkins.get("new").unwrap().is_some());
+        assert_eq!(read_txn.open_table(CHECKIN_TIME_TABLE).unwrap().len().unwrap(), 1);
+    }
+
+    fn stored_rollout(db: &Database, key: &str) -> Option<Rollout> {
+        let read_txn = db.begin_read().ok()?;
+        let table = read_txn.open_table(ROLLOUTS_TABLE).ok()?;
+        table.get(key).unwrap().map(|v| serde_json::from_slice(v.value()).unwrap())
+    }
+
+    #[test]
+    fn update_rollout_stores_changes_per_environment() {
+        let (_dir, db) = temp_db();
+        let candidate = Rollout {
+            stable: Some("aaa".to_string()),
+            candidate: Some("bbb".to_string()),
+            ..Rollout::default()
+        };
+
+        let stored = update_rollout_db(&db, "lab", |r| *r = candidate.clone()).unwrap();
+        assert_eq!(stored, candidate);
+        update_rollout_db(&db, "lab", |r| { r.passed.insert("dev-1".to_string()); }).unwrap();
+
+        let lab = stored_rollout(&db, "lab").unwrap();
+        assert_eq!(lab.candidate.as_deref(), Some("bbb"));
+        assert!(lab.passed.contains("dev-1"));
+        assert_eq!(stored_rollout(&db, ""), None);
+    }
+
+    #[test]
+    fn update_rollout_skips_the_write_when_nothing_changed() {
+        let (_dir, db) = temp_db();
+        assert_eq!(update_rollout_db(&db, "", |_| {}).unwrap(), Rollout::default());
+        assert!(!table_exists(&db, ROLLOUTS_TABLE));
+    }
+
+    #[test]
+    fn update_alert_state_returns_the_previous_state_only_on_change() {
+        let (_dir, db) = temp_db();
+        let fail = |s: &mut AlertState| if !s.failing {
+            *s = AlertState { failing: true, since: "20240105093000".to_string() };
+        };
+
+        assert_eq!(update_alert_state_db(&db, "LAB-1", fail).unwrap(), Some(AlertState::default()));
+        assert_eq!(update_alert_state_db(&db, "LAB-1", fail).unwrap(), None);
+        let previous = update_alert_state_db(&db, "LAB-1", |s| s.failing = false).unwrap().unwrap();
+        assert!(previous.failing);
+        assert_eq!(previous.since, "20240105093000");
+    }
+}
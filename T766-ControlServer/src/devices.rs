@@ -0,0 +1,144 @@
+/// Device tokens: each client holds one, bound to its hostname, and sends it
+/// as a bearer token to `/puppet-sync`, `/manifests`, `/pause` and `/data`.
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A known host and whether it has a token, for the Devices page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub hostname: String,
+    /// When its current token was issued.
+    pub token_issued: Option<i64>,
+    /// Last report, YYYYMMDDHHmmSS.
+    pub last_seen: Option<String>,
+}
+
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Device {
+    pub hostname: String,
+    pub created: i64,
+}
+
+#[cfg(feature = "server")]
+use axum::http::{HeaderMap, StatusCode, header};
+
+/// Tokens are stored by their hash, like sessions.
+#[cfg(feature = "server")]
+fn token_key(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(feature = "server")]
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION)?
+        .to_str().ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Who a request claims to be. `Ok(None)` means no token was sent, which is
+/// only accepted while `require_device_tokens` is off.
+#[cfg(feature = "server")]
+fn authenticate(headers: &HeaderMap) -> Result<Option<Device>, String> {
+    let Some(token) = bearer_token(headers) else {
+        return if crate::config::CONFIG.require_device_tokens {
+            Err("Missing device token".to_string())
+        } else {
+            Ok(None)
+        };
+    };
+    match crate::db::get_device(&token_key(token)) {
+        Ok(Some(device)) => Ok(Some(device)),
+        Ok(None) => Err("Unknown device token".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Middleware for the routes that hand out configuration. The authenticated
+/// device is passed on as a request extension.
+#[cfg(feature = "server")]
+pub async fn require_device(
+    mut request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    use axum::response::IntoResponse;
+    match authenticate(request.headers()) {
+        Ok(device) => {
+            if let Some(device) = device {
+                request.extensions_mut().insert(device);
+            }
+            next.run(request).await
+        }
+        Err(e) => (StatusCode::UNAUTHORIZED, e).into_response(),
+    }
+}
+
+/// Whether `hostname` has been issued a token. Once it has, requests that
+/// claim to be it without one are refused even while tokens are optional.
+#[cfg(feature = "server")]
+pub fn has_token(hostname: &str) -> Result<bool, ServerFnError> {
+    Ok(crate::db::iter_devices()?.iter().any(|d| d.hostname.eq_ignore_ascii_case(hostname)))
+}
+
+/// Check that the current server function call comes from the device for `hostname`.
+#[cfg(feature = "server")]
+pub async fn authorize_host(hostname: &str) -> Result<(), ServerFnError> {
+    let headers = dioxus::fullstack::FullstackContext::extract::<HeaderMap, _>().await?;
+    match authenticate(&headers).map_err(ServerFnError::new)? {
+        Some(device) if !device.hostname.eq_ignore_ascii_case(hostname) => {
+            println!("Rejected report for {} sent with the token for {}", hostname, device.hostname);
+            Err(ServerFnError::new(format!("Device token is not for {}", hostname)))
+        }
+        None if has_token(hostname)? => {
+            println!("Rejected report for {} sent without its device token", hostname);
+            Err(ServerFnError::new(format!("{} has a device token and must send it", hostname)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Every host that has reported or has a token, sorted by hostname.
+#[server]
+pub async fn list_devices() -> Result<Vec<DeviceInfo>, ServerFnError> {
+    use std::collections::BTreeMap;
+
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let mut devices: BTreeMap<String, DeviceInfo> = BTreeMap::new();
+    for (hostname, seen) in crate::db::iter_last_seen()? {
+        devices.insert(hostname.clone(), DeviceInfo { hostname, token_issued: None, last_seen: Some(seen.timestamp) });
+    }
+    for device in crate::db::iter_devices()? {
+        devices.entry(device.hostname.clone())
+            .or_insert(DeviceInfo { hostname: device.hostname, token_issued: None, last_seen: None })
+            .token_issued = Some(device.created);
+    }
+    Ok(devices.into_values().collect())
+}
+
+/// Issue a new token for `hostname`, replacing any it had. The token is only
+/// ever shown here; the server keeps its hash.
+#[server]
+pub async fn issue_device_token(hostname: String) -> Result<String, ServerFnError> {
+    use rand_core::RngCore;
+
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let hostname = hostname.trim().to_string();
+    if hostname.is_empty() {
+        return Err(ServerFnError::new("Hostname is required"));
+    }
+    let mut bytes = [0u8; 32];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+    let device = Device { hostname, created: chrono::Local::now().timestamp() };
+    crate::db::replace_device(&token_key(&token), &device)?;
+    println!("Issued device token for {}", device.hostname);
+    Ok(token)
+}
+
+#[server]
+pub async fn revoke_device_token(hostname: String) -> Result<(), ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    crate::db::revoke_device(&hostname)
+}
@@ -0,0 +1,138 @@
+/// Host groups: each maps its hosts to a manifest environment, so different
+/// kinds of laptop get different bundles from `/manifests`.
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    /// `/puppet/environments/<environment>` on disk, or the branch of that
+    /// name in `manifest_repo`.
+    pub environment: String,
+    /// Hostname globs like `CAD-*`, used for hosts that aren't assigned explicitly.
+    pub patterns: Vec<String>,
+    /// Hosts assigned on the Groups page. These win over any pattern.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+/// A known host and the group it falls in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostGroup {
+    pub hostname: String,
+    pub group: Option<String>,
+    /// Whether it was assigned by hand rather than matched by a pattern.
+    pub explicit: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GroupsOverview {
+    /// Sorted by name, which is also the order patterns are tried in.
+    pub groups: Vec<Group>,
+    /// Every host that has reported, has a token or is assigned, by hostname.
+    pub hosts: Vec<HostGroup>,
+}
+
+/// Whether `hostname` matches a glob where `*` is any run of characters and
+/// `?` any one. Hostnames are compared case-insensitively.
+#[cfg(feature = "server")]
+pub fn pattern_matches(pattern: &str, hostname: &str) -> bool {
+    let pattern = regex::escape(pattern).replace(r"\*", ".*").replace(r"\?", ".");
+    regex::Regex::new(&format!("(?i)^{}$", pattern)).is_ok_and(|re| re.is_match(hostname))
+}
+
+/// The group `hostname` belongs to: the one it's assigned to, otherwise the
+/// first by name with a matching pattern. `groups` must be sorted by name.
+#[cfg(feature = "server")]
+pub fn group_for<'a>(groups: &'a [Group], hostname: &str) -> Option<&'a Group> {
+    groups.iter()
+        .find(|g| g.hosts.iter().any(|h| h.eq_ignore_ascii_case(hostname)))
+        .or_else(|| groups.iter().find(|g| g.patterns.iter().any(|p| pattern_matches(p, hostname))))
+}
+
+/// Environment names end up in paths and git refs, so keep them plain.
+#[cfg(feature = "server")]
+fn is_valid_environment(environment: &str) -> bool {
+    !environment.is_empty()
+        && !environment.starts_with(['.', '-'])
+        && environment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !environment.contains("..")
+}
+
+/// The environment `hostname` should be served, or `None` for the default bundle.
+#[cfg(feature = "server")]
+pub fn environment_for(hostname: &str) -> Result<Option<String>, ServerFnError> {
+    let groups = crate::db::iter_groups()?;
+    Ok(group_for(&groups, hostname).map(|g| g.environment.clone()))
+}
+
+#[server]
+pub async fn list_groups() -> Result<GroupsOverview, ServerFnError> {
+    use std::collections::BTreeSet;
+
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let groups = crate::db::iter_groups()?;
+    let mut hostnames: BTreeSet<String> = crate::db::iter_last_seen()?.into_iter().map(|(h, _)| h).collect();
+    hostnames.extend(crate::db::iter_devices()?.into_iter().map(|d| d.hostname));
+    hostnames.extend(groups.iter().flat_map(|g| g.hosts.iter().cloned()));
+
+    let hosts = hostnames.into_iter()
+        .map(|hostname| {
+            let group = group_for(&groups, &hostname);
+            HostGroup {
+                explicit: group.is_some_and(|g| g.hosts.iter().any(|h| h.eq_ignore_ascii_case(&hostname))),
+                group: group.map(|g| g.name.clone()),
+                hostname,
+            }
+        })
+        .collect();
+    Ok(GroupsOverview { groups, hosts })
+}
+
+/// Create a group, or change an existing one's environment and patterns.
+/// `patterns` is separated by commas or whitespace.
+#[server]
+pub async fn save_group(name: String, environment: String, patterns: String) -> Result<(), ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let name = name.trim().to_string();
+    let environment = environment.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::new("Name is required"));
+    }
+    if !is_valid_environment(&environment) {
+        return Err(ServerFnError::new("Environment may only use letters, digits, '-', '_' and '.'"));
+    }
+    let patterns = patterns.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect();
+    let hosts = crate::db::iter_groups()?.into_iter()
+        .find(|g| g.name == name)
+        .map(|g| g.hosts)
+        .unwrap_or_default();
+    crate::db::put_groups(&[Group { name, environment, patterns, hosts }])
+}
+
+/// Remove a group. Its hosts fall back to patterns, or the default bundle.
+#[server]
+pub async fn delete_group(name: String) -> Result<(), ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    crate::db::delete_group(&name)
+}
+
+/// Put `hostname` in `group`, or back on pattern matching if `group` is empty.
+#[server]
+pub async fn assign_host(hostname: String, group: String) -> Result<(), ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let mut groups = crate::db::iter_groups()?;
+    if !group.is_empty() && !groups.iter().any(|g| g.name == group) {
+        return Err(ServerFnError::new(format!("No group named {}", group)));
+    }
+    for g in &mut groups {
+        g.hosts.retain(|h| !h.eq_ignore_ascii_case(&hostname));
+        if g.name == group {
+            g.hosts.push(hostname.clone());
+        }
+    }
+    crate::db::put_groups(&groups)
+}
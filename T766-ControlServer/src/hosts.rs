@@ -0,0 +1,283 @@
+/// Per-host hardware and OS inventory reported by clients.
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::checkins::CheckinLogEntry;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: Option<String>,
+    pub ips: Vec<String>,
+}
+
+/// Facts collected by the client, from facter when available.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct HostFacts {
+    pub source: String,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    pub kernel: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_count: Option<u32>,
+    pub memory_total_bytes: Option<u64>,
+    pub disk_total_bytes: Option<u64>,
+    pub disk_free_bytes: Option<u64>,
+    pub serial_number: Option<String>,
+    pub interfaces: Vec<NetworkInterface>,
+    pub battery_health_percent: Option<f64>,
+    pub uptime_seconds: Option<u64>,
+}
+
+/// Latest facts for a host and when they were reported (YYYYMMDDHHmmSS).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostInventory {
+    pub hostname: String,
+    pub timestamp: String,
+    pub facts: HostFacts,
+}
+
+/// One puppet run as listed on the host page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostRun {
+    pub timestamp: String,
+    /// Sync table interval the run falls in, for linking to its logs.
+    pub interval: i64,
+    pub status: String,
+    pub exit_code: i32,
+    pub commit: Option<String>,
+    /// Applied from the client's cached bundle while the server was unreachable.
+    pub offline: bool,
+}
+
+/// Share of successful runs on one day.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DailySuccess {
+    pub day: String,
+    pub runs: u32,
+    pub successes: u32,
+}
+
+/// Everything the host page shows about one laptop.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostHistory {
+    pub hostname: String,
+    pub last_seen: Option<String>,
+    /// Manifest repository commit of the newest run.
+    pub commit: Option<String>,
+    /// Newest first.
+    pub runs: Vec<HostRun>,
+    /// Oldest first.
+    pub success_by_day: Vec<DailySuccess>,
+    /// Newest first, with student codes replaced by names. Only sent to mentors.
+    pub checkins: Option<Vec<CheckinLogEntry>>,
+    pub inventory: Option<HostInventory>,
+}
+
+/// When a host last reported and the schedule it was on.
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LastSeen {
+    pub timestamp: String,
+    pub schedule: Option<String>,
+    /// Manifest repository commit the host last applied.
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// Whether the silent-host alert has gone out since the last report.
+    #[serde(default)]
+    pub alerted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum HostState {
+    Ok,
+    Stale,
+    Offline,
+}
+
+/// A host that has stopped reporting on schedule.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostHealth {
+    pub hostname: String,
+    pub last_seen: String,
+    pub missed_intervals: u32,
+    pub state: HostState,
+}
+
+/// Schedule assumed for hosts that never reported one: the client's
+/// `DEFAULT_SCHEDULE`, in the seconds-first form reports use. The crates share
+/// no code, so change both together.
+#[cfg(feature = "server")]
+const DEFAULT_SCHEDULE: &str = "0 0,30 * * * *";
+
+/// Count the scheduled syncs since the host last reported and classify it.
+#[cfg(feature = "server")]
+fn host_health(hostname: &str, seen: &LastSeen, now: chrono::DateTime<chrono::Local>) -> HostHealth {
+    use crate::config::CONFIG;
+    use std::str::FromStr;
+
+    let schedule = seen.schedule.as_deref()
+        .and_then(|s| cron::Schedule::from_str(s).ok())
+        .unwrap_or_else(|| cron::Schedule::from_str(DEFAULT_SCHEDULE).expect("default schedule is valid"));
+
+    // The first slot after a report may still be running inside the client's splay,
+    // so it only counts once the one after it has passed as well.
+    let missed_intervals = chrono::NaiveDateTime::parse_from_str(&seen.timestamp, "%Y%m%d%H%M%S")
+        .ok()
+        .and_then(|t| t.and_local_timezone(chrono::Local).earliest())
+        .map(|last| {
+            schedule.after(&last)
+                .take_while(|t| *t <= now)
+                .take(CONFIG.offline_after_intervals as usize + 1)
+                .count() as u32
+        })
+        .unwrap_or_default()
+        .saturating_sub(1);
+
+    let state = if missed_intervals >= CONFIG.offline_after_intervals {
+        HostState::Offline
+    } else if missed_intervals >= CONFIG.stale_after_intervals {
+        HostState::Stale
+    } else {
+        HostState::Ok
+    };
+
+    HostHealth {
+        hostname: hostname.to_string(),
+        last_seen: seen.timestamp.clone(),
+        missed_intervals,
+        state,
+    }
+}
+
+/// Hosts that are stale or offline, longest silent first.
+#[server]
+pub async fn get_silent_hosts() -> Result<Vec<HostHealth>, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Viewer).await?;
+    let now = chrono::Local::now();
+    let mut hosts: Vec<HostHealth> = crate::db::iter_last_seen()?
+        .iter()
+        .map(|(hostname, seen)| host_health(hostname, seen, now))
+        .filter(|h| h.state != HostState::Ok)
+        .collect();
+    hosts.sort_by(|a, b| a.last_seen.cmp(&b.last_seen));
+    Ok(hosts)
+}
+
+/// Periodically check for hosts that went offline and alert once per silence.
+#[cfg(feature = "server")]
+pub async fn monitor() {
+    use crate::alerts::{self, Alert};
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let hosts = match crate::db::iter_last_seen() {
+            Ok(hosts) => hosts,
+            Err(e) => {
+                println!("Failed to read last seen hosts: {}", e);
+                continue;
+            }
+        };
+
+        let now = chrono::Local::now();
+        for (hostname, seen) in hosts.iter().filter(|(_, seen)| !seen.alerted) {
+            let health = host_health(hostname, seen, now);
+            if health.state != HostState::Offline {
+                continue;
+            }
+            let alert = Alert::HostSilent {
+                hostname: hostname.clone(),
+                last_seen: seen.timestamp.clone(),
+                missed_intervals: health.missed_intervals,
+            };
+            if alerts::send(&alert).await
+                && let Err(e) = crate::db::mark_alerted(hostname, &seen.timestamp)
+            {
+                println!("Failed to record alert for {}: {}", hostname, e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+const HISTORY_RUNS: usize = 50;
+#[cfg(feature = "server")]
+const HISTORY_DAYS: usize = 14;
+#[cfg(feature = "server")]
+const HISTORY_CHECKINS: usize = 50;
+
+/// Run history, recent checkins and facts for a single host.
+#[server]
+pub async fn get_host_history(hostname: String) -> Result<HostHistory, ServerFnError> {
+    use crate::auth::Role;
+    use crate::checkins::load_people_map;
+    use crate::sync::interval_index;
+    use std::collections::BTreeMap;
+
+    let user = crate::auth::require_role(Role::StudentLead).await?;
+
+    // Newest first: everything in the charted days, or the last HISTORY_RUNS if that's more.
+    let since = chrono::Local::now().timestamp() - HISTORY_DAYS as i64 * 86400;
+    let mut syncs = crate::db::host_runs(&hostname, since.., usize::MAX)?;
+    if syncs.len() < HISTORY_RUNS {
+        syncs = crate::db::host_runs(&hostname, .., HISTORY_RUNS)?;
+    }
+
+    let mut by_day: BTreeMap<String, (u32, u32)> = BTreeMap::new();
+    for s in &syncs {
+        let Some(day) = s.timestamp.get(..8) else { continue };
+        let entry = by_day.entry(day.to_string()).or_default();
+        entry.0 += 1;
+        if matches!(s.status.as_str(), "success" | "changed") {
+            entry.1 += 1;
+        }
+    }
+    let success_by_day = by_day.into_iter()
+        .rev()
+        .take(HISTORY_DAYS)
+        .rev()
+        .map(|(day, (runs, successes))| DailySuccess { day, runs, successes })
+        .collect();
+
+    let checkins = if user.role >= Role::Mentor {
+        let people = load_people_map();
+        Some(crate::db::recent_checkins(Some(&hostname), HISTORY_CHECKINS)?
+            .iter()
+            .map(|c| c.to_entry(&people))
+            .collect())
+    } else {
+        None
+    };
+
+    let runs = syncs.iter()
+        .take(HISTORY_RUNS)
+        .map(|s| HostRun {
+            timestamp: s.timestamp.clone(),
+            interval: interval_index(&s.timestamp).unwrap_or_default(),
+            status: s.status.clone(),
+            exit_code: s.exit_code,
+            commit: s.commit.clone(),
+            offline: s.offline,
+        })
+        .collect();
+
+    Ok(HostHistory {
+        last_seen: syncs.first().map(|s| s.timestamp.clone()),
+        commit: syncs.first().and_then(|s| s.commit.clone()),
+        runs,
+        success_by_day,
+        checkins,
+        inventory: crate::db::get_facts(&hostname)?,
+        hostname,
+    })
+}
+
+/// Latest facts for every host, sorted by hostname.
+#[server]
+pub async fn get_inventory() -> Result<Vec<HostInventory>, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::StudentLead).await?;
+    let mut hosts = crate::db::iter_facts()?;
+    hosts.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+    Ok(hosts)
+}
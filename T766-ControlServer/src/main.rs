@@ -1,17 +1,36 @@
 #![allow(non_snake_case)]
 use dioxus::prelude::*;
-use chrono::{Local, Timelike};
-use urlencoding::{encode, decode};
+use chrono::Local;
 
+#[cfg(feature = "server")]
+mod alerts;
+mod attendance;
+mod auth;
+#[cfg(feature = "server")]
+mod config;
 #[cfg(feature = "server")]
 mod db;
+mod devices;
+mod groups;
 mod sync;
 mod checkins;
+mod hosts;
 #[cfg(feature = "server")]
 mod manifests;
+mod pause;
+mod people;
+mod rollouts;
 
 use sync::*;
 use checkins::*;
+use hosts::*;
+use attendance::*;
+use auth::*;
+use devices::*;
+use groups::*;
+use pause::*;
+use people::*;
+use rollouts::*;
 
 // --- Server entry point ---
 
@@ -47,12 +66,77 @@ async fn main() {
         (StatusCode::OK, format!("{:x}", hasher.finalize()))
     }
 
+    if let Err(e) = db::migrate() {
+        println!("Failed to migrate database: {}", e);
+    }
+    people::import_legacy_csv();
+
+    #[derive(serde::Deserialize)]
+    struct DateRange {
+        start: String,
+        end: String,
+    }
+
+    async fn attendance_csv(
+        headers: axum::http::HeaderMap,
+        axum::extract::Query(range): axum::extract::Query<DateRange>,
+    ) -> impl IntoResponse {
+        if !auth::has_role(&headers, Role::Mentor) {
+            return (StatusCode::FORBIDDEN, "Mentors only").into_response();
+        }
+        match attendance::build_report(&range.start, &range.end).and_then(|r| attendance::to_csv(&r)) {
+            Ok(csv) => (
+                StatusCode::OK,
+                [
+                    (axum::http::header::CONTENT_TYPE, "text/csv".to_string()),
+                    (axum::http::header::CONTENT_DISPOSITION, format!(
+                        "attachment; filename=\"attendance-{}-to-{}.csv\"", range.start, range.end
+                    )),
+                ],
+                csv,
+            ).into_response(),
+            Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+        }
+    }
+
+    async fn people_csv(headers: axum::http::HeaderMap) -> impl IntoResponse {
+        if !auth::has_role(&headers, Role::Mentor) {
+            return (StatusCode::FORBIDDEN, "Mentors only").into_response();
+        }
+        match people::to_csv() {
+            Ok(csv) => (
+                StatusCode::OK,
+                [
+                    (axum::http::header::CONTENT_TYPE, "text/csv"),
+                    (axum::http::header::CONTENT_DISPOSITION, "attachment; filename=\"people.csv\""),
+                ],
+                csv,
+            ).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        }
+    }
+
+    println!("Manifest signing public key: {}", manifests::public_key_hex());
+    tokio::spawn(hosts::monitor());
+    tokio::spawn(db::prune_task());
+
     let address = dioxus::cli_config::fullstack_address_or_localhost();
 
-    let router = axum::Router::new()
+    // Configuration is only handed to clients with a device token.
+    let device_routes = axum::Router::new()
         .nest_service("/data", ServeDir::new("/puppet"))
         .route("/data/hashes/{filename}", axum::routing::get(hash_handler))
         .route("/manifests", axum::routing::get(manifests::handler))
+        .route("/pause", axum::routing::get(pause::status_handler))
+        .layer(axum::middleware::from_fn(devices::require_device));
+
+    let router = axum::Router::new()
+        .merge(device_routes)
+        .route("/export/attendance.csv", axum::routing::get(attendance_csv))
+        .route("/export/people.csv", axum::routing::get(people_csv))
+        .route("/auth/login", axum::routing::post(auth::login_handler))
+        .route("/auth/setup", axum::routing::post(auth::setup_handler))
+        .route("/auth/logout", axum::routing::post(auth::logout_handler))
         .serve_dioxus_application(dioxus_server::ServeConfig::new(), App);
 
     let listener = tokio::net::TcpListener::bind(address).await.unwrap();
@@ -71,12 +155,30 @@ fn main() {
 enum Route {
     #[route("/")]
     Home {},
-    #[route("/logs/:time/:hostname")]
-    Logs { time: String, hostname: String },
+    #[route("/logs/:interval/:hostname")]
+    Logs { interval: i64, hostname: String },
     #[route("/checkins")]
     Checkins {},
-    #[route("/checkin/:hostname/:log_text")]
-    CheckinLog { hostname: String, log_text: String },
+    #[route("/checkin/:id")]
+    CheckinLog { id: String },
+    #[route("/attendance")]
+    Attendance {},
+    #[route("/roster")]
+    Roster {},
+    #[route("/devices")]
+    Devices {},
+    #[route("/groups")]
+    Groups {},
+    #[route("/rollouts")]
+    Rollouts {},
+    #[route("/users")]
+    Users {},
+    #[route("/inventory")]
+    Inventory {},
+    #[route("/host/:hostname")]
+    Host { hostname: String },
+    #[route("/login?:error")]
+    Login { error: String },
 }
 
 #[component]
@@ -90,29 +192,167 @@ fn App() -> Element {
 
 // --- Shared UI ---
 
-/// Page wrapper with header, nav link, and sync countdown.
+/// Page wrapper with header, section nav, and sync countdown. Visitors who
+/// aren't logged in get the login form instead.
 #[component]
-fn Page(title: String, nav_label: String, nav_to: Route, children: Element) -> Element {
-    rsx! {
-        div { class: "min-h-screen bg-neutral p-6",
-            div { class: "max-w-6xl mx-auto",
-                div { class: "flex justify-between items-center mb-8 pb-4 border-b border-neutral-content/10",
-                    h1 { class: "text-2xl font-light tracking-wide text-neutral-content", "{title}" }
-                    Link {
-                        to: nav_to,
-                        class: "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider",
-                        "{nav_label}"
+fn Page(title: String, children: Element) -> Element {
+    let auth = use_resource(move || async move { get_auth_status().await });
+    let route = use_route::<Route>();
+    // Each section and the least role that can use it.
+    let sections = [
+        ("Syncs", Route::Home {}, Role::Viewer),
+        ("Checkins", Route::Checkins {}, Role::Mentor),
+        ("Attendance", Route::Attendance {}, Role::Mentor),
+        ("Roster", Route::Roster {}, Role::Mentor),
+        ("Inventory", Route::Inventory {}, Role::StudentLead),
+        ("Devices", Route::Devices {}, Role::Mentor),
+        ("Groups", Route::Groups {}, Role::Mentor),
+        ("Rollouts", Route::Rollouts {}, Role::StudentLead),
+        ("Users", Route::Users {}, Role::Mentor),
+    ];
+    let nav_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+
+    match &*auth.read_unchecked() {
+        Some(Ok(AuthStatus::LoggedIn(user))) => rsx! {
+            div { class: "min-h-screen bg-neutral p-6",
+                div { class: "max-w-6xl mx-auto",
+                    div { class: "flex justify-between items-center mb-8 pb-4 border-b border-neutral-content/10",
+                        h1 { class: "text-2xl font-light tracking-wide text-neutral-content", "{title}" }
+                        nav { class: "flex items-center gap-6",
+                            for (label, to, _) in sections.into_iter().filter(|(_, _, role)| user.role >= *role) {
+                                Link { to, class: nav_class, "{label}" }
+                            }
+                            form { method: "post", action: "/auth/logout",
+                                button {
+                                    r#type: "submit",
+                                    class: nav_class,
+                                    title: format!("Logged in as {} ({})", user.username, user.role.label()),
+                                    "Log out"
+                                }
+                            }
+                        }
                     }
+                    {children}
+                }
+                div { class: "fixed bottom-6 right-6 z-50 text-neutral-content/80",
+                    SyncCountdown {}
                 }
-                {children}
             }
-            div { class: "fixed bottom-6 right-6 z-50 text-neutral-content/80",
-                SyncCountdown {}
+        },
+        Some(Ok(status)) => rsx! {
+            LoginForm { setup: *status == AuthStatus::NeedsSetup, next: route.to_string(), error: String::new() }
+        },
+        Some(Err(e)) => rsx! { EmptyState { message: e.to_string() } },
+        None => rsx! { EmptyState { message: "Loading..." } },
+    }
+}
+
+/// Username and password form, or the first-run admin form when `setup` is set.
+/// Posts straight to the `/auth` routes, which set the session cookie and redirect to `next`.
+#[component]
+fn LoginForm(setup: bool, next: String, error: String) -> Element {
+    let input_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm focus:outline-none focus:border-neutral-content/30";
+    let (action, heading) = if setup {
+        ("/auth/setup", "Create the admin account")
+    } else {
+        ("/auth/login", "Log in")
+    };
+
+    rsx! {
+        div { class: "min-h-screen bg-neutral flex items-center justify-center p-6",
+            form { class: "flex flex-col gap-3 w-80", method: "post", action,
+                h1 { class: "text-2xl font-light tracking-wide text-neutral-content mb-4", "{heading}" }
+                if !error.is_empty() {
+                    div { class: "text-sm text-error", "{error}" }
+                }
+                input { class: input_class, name: "username", placeholder: "Username", autocomplete: "username", required: true }
+                input {
+                    class: input_class,
+                    r#type: "password",
+                    name: "password",
+                    placeholder: "Password",
+                    autocomplete: if setup { "new-password" } else { "current-password" },
+                    required: true,
+                }
+                if setup {
+                    input { class: input_class, r#type: "password", name: "confirm", placeholder: "Confirm password", autocomplete: "new-password", required: true }
+                }
+                input { r#type: "hidden", name: "next", value: next }
+                button {
+                    r#type: "submit",
+                    class: "mt-2 px-3 py-2 rounded-lg bg-neutral-content/10 hover:bg-neutral-content/20 text-neutral-content text-sm transition-colors",
+                    "{heading}"
+                }
             }
         }
     }
 }
 
+/// Where failed logins land, with the reason from the `/auth` routes.
+#[component]
+fn Login(error: String) -> Element {
+    let auth = use_resource(move || async move { get_auth_status().await });
+    let message = match error.as_str() {
+        "" => "",
+        "invalid" => "Wrong username or password",
+        "username" => "Enter a username",
+        "short" => "Passwords need at least 8 characters",
+        "mismatch" => "Passwords don't match",
+        "exists" => "An admin account already exists",
+        _ => "Login failed",
+    };
+    let setup = matches!(&*auth.read_unchecked(), Some(Ok(AuthStatus::NeedsSetup)));
+
+    rsx! { LoginForm { setup, next: "/", error: message } }
+}
+
+/// Background colour for a sync status.
+fn status_color(status: &str) -> &'static str {
+    match status {
+        "success" => "bg-success",
+        "changed" => "bg-info",
+        "changed-failure" => "bg-error ring-2 ring-info",
+        // Offline runs were stored under their own status before `offline` was a flag.
+        "offline-cached" => "bg-warning",
+        "paused" => "bg-neutral-content/40",
+        _ => "bg-error",
+    }
+}
+
+/// Outline marking a run applied from the client's cached bundle.
+fn offline_marker(offline: bool) -> &'static str {
+    if offline { "outline outline-2 outline-offset-2 outline-warning" } else { "" }
+}
+
+/// Human-readable size, e.g. "15.6 GB".
+fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, units[unit])
+}
+
+/// Display a YYYYMMDDHHmmSS timestamp in the same style as the sync table.
+fn format_timestamp(timestamp: &str) -> String {
+    chrono::NaiveDateTime::parse_from_str(timestamp, "%Y%m%d%H%M%S")
+        .map(|t| t.format("%-I:%M %p %-m-%-d-%y").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// Abbreviated commit ID, as git shows it.
+fn short_commit(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
+
+/// Whether puppet changed anything on the host during the run.
+fn is_changed(status: &str) -> bool {
+    matches!(status, "changed" | "changed-failure")
+}
+
 #[component]
 fn StatusDot(success: bool) -> Element {
     let color = if success { "bg-success" } else { "bg-error" };
@@ -127,13 +367,23 @@ fn EmptyState(message: String) -> Element {
 #[component]
 fn SyncCountdown() -> Element {
     let mut countdown = use_signal(|| String::new());
+    let mut next_sync = use_resource(move || async move { get_next_sync().await.ok().flatten() });
 
     use_future(move || async move {
         loop {
-            let now = Local::now();
-            let minute = now.minute();
-            let mins = if minute < 30 { 30 - minute } else { 60 - minute };
-            countdown.set(format!("{} minutes to sync", mins));
+            let next = (*next_sync.read()).flatten();
+            match next {
+                Some(next) => {
+                    let secs = next - Local::now().timestamp();
+                    if secs <= 0 {
+                        countdown.set("Syncing now".to_string());
+                        next_sync.restart();
+                    } else {
+                        countdown.set(format!("{} minutes to sync", (secs + 59) / 60));
+                    }
+                }
+                None => countdown.set(String::new()),
+            }
             gloo_timers::future::sleep(std::time::Duration::from_secs(1)).await;
         }
     });
@@ -150,13 +400,13 @@ fn Home() -> Element {
     rsx! {
         Page {
             title: "Control Node",
-            nav_label: "Laptop Checkins",
-            nav_to: Route::Checkins {},
             button {
                 class: "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider mb-4",
                 onclick: move |_| sync_data.restart(),
                 "Refresh"
             }
+            PauseControl {}
+            SilentHosts {}
             match &*sync_data.read_unchecked() {
                 Some(Some(data)) => rsx! { SyncTable { data: data.clone() } },
                 Some(None) => rsx! { EmptyState { message: "No sync data" } },
@@ -166,34 +416,192 @@ fn Home() -> Element {
     }
 }
 
+/// Whether applies are paused fleet-wide, with the switch for mentors.
+#[component]
+fn PauseControl() -> Element {
+    let mut pause = use_resource(move || async move { get_pause().await });
+    let auth = use_resource(move || async move { get_auth_status().await });
+    let mut hours = use_signal(String::new);
+    let mut reason = use_signal(String::new);
+    let mut error = use_signal(|| None::<String>);
+
+    let is_mentor = matches!(&*auth.read_unchecked(), Some(Ok(AuthStatus::LoggedIn(user))) if user.role >= Role::Mentor);
+    let input_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm focus:outline-none focus:border-neutral-content/30";
+    let button_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+
+    let start = move |_| async move {
+        match pause_applies(hours().parse().ok(), reason()).await {
+            Ok(()) => {
+                error.set(None);
+                reason.set(String::new());
+                pause.restart();
+            }
+            Err(e) => error.set(Some(e.to_string())),
+        }
+    };
+    let resume = move |_| async move {
+        match resume_applies().await {
+            Ok(()) => {
+                error.set(None);
+                pause.restart();
+            }
+            Err(e) => error.set(Some(e.to_string())),
+        }
+    };
+
+    rsx! {
+        match &*pause.read_unchecked() {
+            Some(Ok(Some(p))) => rsx! {
+                div { class: "flex items-center gap-6 mb-6 p-4 border border-warning/40 rounded-lg text-sm text-warning",
+                    span {
+                        "Applies are paused"
+                        {p.until
+                            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                            .map(|t| format!(" until {}", t.with_timezone(&Local).format("%-m-%-d-%y %-I:%M %p")))
+                            .unwrap_or_default()}
+                        {format!(" by {}", p.by)}
+                        if !p.reason.is_empty() {
+                            {format!(": {}", p.reason)}
+                        }
+                    }
+                    if is_mentor {
+                        button { class: "ml-auto {button_class}", onclick: resume, "Resume applies" }
+                    }
+                }
+            },
+            Some(Ok(None)) if is_mentor => rsx! {
+                div { class: "flex items-center gap-4 mb-6",
+                    select {
+                        class: input_class,
+                        onchange: move |e| hours.set(e.value()),
+                        option { value: "", "Until resumed" }
+                        for h in [1, 4, 12, 24, 72] {
+                            option { value: "{h}", {format!("For {} hours", h)} }
+                        }
+                    }
+                    input { class: input_class, placeholder: "Reason", value: "{reason}", oninput: move |e| reason.set(e.value()) }
+                    button { class: button_class, onclick: start, "Pause applies" }
+                }
+            },
+            _ => rsx! {},
+        }
+        if let Some(error) = error() {
+            div { class: "mb-6 text-sm text-error", "{error}" }
+        }
+    }
+}
+
+/// Hosts that have missed scheduled syncs; hidden when every host is reporting.
+#[component]
+fn SilentHosts() -> Element {
+    let silent = use_resource(move || async move { get_silent_hosts().await.ok() });
+
+    rsx! {
+        if let Some(Some(hosts)) = &*silent.read_unchecked() {
+            if !hosts.is_empty() {
+                div { class: "mb-8 border border-neutral-content/10 rounded-lg px-4 py-3",
+                    h2 { class: "text-xs text-neutral-content/50 uppercase tracking-wider mb-3", "Not reporting" }
+                    for host in hosts.iter() {
+                        div { class: "flex items-center gap-4 py-1 text-sm",
+                            div {
+                                class: if host.state == HostState::Offline { "w-2 h-2 rounded-full bg-error" } else { "w-2 h-2 rounded-full bg-warning" },
+                            }
+                            Link {
+                                to: Route::Host { hostname: host.hostname.clone() },
+                                class: "font-mono text-neutral-content/70 hover:text-neutral-content",
+                                "{host.hostname}"
+                            }
+                            span { class: "text-xs text-neutral-content/50",
+                                if host.state == HostState::Offline { "Offline" } else { "Stale" }
+                                {format!(" · missed {} syncs · last seen {}", host.missed_intervals, format_timestamp(&host.last_seen))}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 fn SyncTable(data: SyncTableData) -> Element {
+    let mut changed_only = use_signal(|| false);
+
+    let hostnames: Vec<String> = if changed_only() {
+        data.hostnames.iter()
+            .filter(|h| data.syncs.values().any(|hosts| hosts.get(*h).is_some_and(|s| is_changed(s))))
+            .cloned()
+            .collect()
+    } else {
+        data.hostnames.clone()
+    };
+    // Each host's last applied commit, and whether it differs from the one it's
+    // served. Keyed by hostname since `hostnames` may be filtered.
+    let commits: std::collections::HashMap<&String, (&String, bool)> = data.hostnames.iter()
+        .zip(data.commits.iter().zip(&data.served_commits))
+        .filter_map(|(hostname, (commit, served))| {
+            let commit = commit.as_ref()?;
+            Some((hostname, (commit, served.as_ref().is_some_and(|s| s != commit))))
+        })
+        .collect();
+
     rsx! {
+        div { class: "flex items-center gap-6 mb-4 text-xs text-neutral-content/50",
+            for (label, status) in [("No changes", "success"), ("Changed", "changed"), ("Failed", "failure"), ("Changed + failed", "changed-failure"), ("Paused", "paused")] {
+                span { class: "flex items-center gap-2",
+                    div { class: format!("w-2 h-2 rounded-full {}", status_color(status)) }
+                    "{label}"
+                }
+            }
+            span { class: "flex items-center gap-2",
+                div { class: format!("w-2 h-2 rounded-full bg-neutral-content/40 {}", offline_marker(true)) }
+                "Applied offline"
+            }
+            label { class: "flex items-center gap-2 ml-auto cursor-pointer uppercase tracking-wider",
+                input {
+                    r#type: "checkbox",
+                    class: "checkbox checkbox-xs",
+                    checked: changed_only(),
+                    onchange: move |evt| changed_only.set(evt.checked()),
+                }
+                "Changed hosts only"
+            }
+        }
         div { class: "overflow-x-auto",
             table { class: "w-full border-collapse",
                 thead {
                     tr { class: "border-b border-neutral-content/10",
                         th { class: "text-left py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider", "Time" }
-                        for hostname in &data.hostnames {
-                            th { class: "text-center py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider", "{hostname}" }
+                        for hostname in &hostnames {
+                            th { class: "text-center py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider",
+                                Link {
+                                    to: Route::Host { hostname: hostname.clone() },
+                                    class: "hover:text-neutral-content transition-colors",
+                                    "{hostname}"
+                                }
+                                if let Some((commit, behind)) = commits.get(hostname).copied() {
+                                    div {
+                                        class: if behind { "font-mono normal-case text-warning" } else { "font-mono normal-case" },
+                                        title: if behind { "Not the commit being served" } else { "" },
+                                        {short_commit(commit)}
+                                    }
+                                }
+                            }
                         }
                     }
                 }
                 tbody {
-                    for time in &data.times {
+                    for (time, interval) in data.times.iter().zip(data.intervals.iter().copied()) {
                         tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors",
                             td { class: "py-3 px-4 text-sm font-mono text-neutral-content/70", "{time}" }
-                            for hostname in &data.hostnames {
+                            for hostname in &hostnames {
                                 td { class: "text-center py-3 px-4",
                                     if let Some(status) = data.syncs.get(time).and_then(|h| h.get(hostname)) {
                                         Link {
-                                            to: Route::Logs { time: time.clone(), hostname: hostname.clone() },
+                                            to: Route::Logs { interval, hostname: hostname.clone() },
                                             div {
-                                                class: if status == "success" {
-                                                    "w-2 h-2 rounded-full bg-success mx-auto cursor-pointer hover:scale-150 transition-transform"
-                                                } else {
-                                                    "w-2 h-2 rounded-full bg-error mx-auto cursor-pointer hover:scale-150 transition-transform"
-                                                },
+                                                class: format!("w-2 h-2 rounded-full {} {} mx-auto cursor-pointer hover:scale-150 transition-transform", status_color(status), offline_marker(data.is_offline(time, hostname))),
+                                                title: if data.is_offline(time, hostname) { "{status}, applied offline" } else { "{status}" },
                                             }
                                         }
                                     } else {
@@ -212,25 +620,26 @@ fn SyncTable(data: SyncTableData) -> Element {
 // --- Logs detail ---
 
 #[component]
-fn Logs(time: String, hostname: String) -> Element {
-    let time_display = time.clone();
+fn Logs(interval: i64, hostname: String) -> Element {
     let hostname_display = hostname.clone();
 
     let log_data = use_resource(move || {
-        let time = time.clone();
         let hostname = hostname.clone();
-        async move { get_logs_for_interval(time, hostname).await.ok() }
+        async move { get_logs_for_interval(interval, hostname).await.ok() }
     });
 
+    let title = match &*log_data.read_unchecked() {
+        Some(Some(data)) => format!("Logs: {} @ {}", hostname_display, data.time),
+        _ => format!("Logs: {}", hostname_display),
+    };
+
     rsx! {
         Page {
-            title: "Logs: {hostname_display} @ {time_display}",
-            nav_label: "Home",
-            nav_to: Route::Home {},
+            title,
             match &*log_data.read_unchecked() {
-                Some(Some(logs)) if !logs.is_empty() => rsx! {
+                Some(Some(data)) if !data.logs.is_empty() => rsx! {
                     div { class: "space-y-4",
-                        for log in logs.iter() {
+                        for log in data.logs.iter() {
                             LogEntry { log: log.clone() }
                         }
                     }
@@ -253,8 +662,10 @@ fn LogEntry(log: PuppetStatus) -> Element {
                 class: "w-full px-4 py-3 flex justify-between items-center hover:bg-neutral-content/5 transition-colors",
                 onclick: move |_| is_open.set(!is_open()),
                 div { class: "flex items-center gap-4",
-                    div {
-                        class: if log.status == "success" { "w-3 h-3 rounded-full bg-success" } else { "w-3 h-3 rounded-full bg-error" },
+                    div { class: format!("w-3 h-3 rounded-full {} {}", status_color(&log.status), offline_marker(log.offline)) }
+                    span { class: "text-xs text-neutral-content/70", "{log.status}" }
+                    if log.offline {
+                        span { class: "text-xs text-warning", "Applied offline from cached manifests" }
                     }
                     span { class: "text-xs text-neutral-content/50", "Exit code: {log.exit_code}" }
                 }
@@ -262,6 +673,9 @@ fn LogEntry(log: PuppetStatus) -> Element {
             }
             if is_open() {
                 div { class: "px-4 py-3 bg-neutral-content/5 border-t border-neutral-content/10",
+                    if let Some(report) = &log.report {
+                        RunSummary { report: report.clone() }
+                    }
                     if !log.logs.is_empty() {
                         pre { class: "text-xs text-neutral-content/70 bg-black/20 p-3 rounded overflow-x-auto font-mono whitespace-pre-wrap", "{log.logs}" }
                     } else {
@@ -273,40 +687,87 @@ fn LogEntry(log: PuppetStatus) -> Element {
     }
 }
 
+/// Resource counts, timings and per-resource events from a structured run report.
+#[component]
+fn RunSummary(report: RunReport) -> Element {
+    let counts = [
+        ("Changed", report.resources.changed),
+        ("Failed", report.resources.failed),
+        ("Skipped", report.resources.skipped),
+        ("Total", report.resources.total),
+    ];
+    let total_time = format!("{:.1}s", report.timing.get("total").copied().unwrap_or_default());
+    let timings: Vec<(String, String)> = report.timing.iter()
+        .filter(|(name, _)| name.as_str() != "total")
+        .map(|(name, secs)| (name.clone(), format!("{:.2}s", secs)))
+        .collect();
+
+    rsx! {
+        div { class: "mb-4 space-y-3",
+            div { class: "flex flex-wrap gap-6 text-xs text-neutral-content/50 uppercase tracking-wider",
+                for (label, count) in counts {
+                    span { "{label} ", span { class: "text-neutral-content", "{count}" } }
+                }
+                span { "Time ", span { class: "text-neutral-content", "{total_time}" } }
+            }
+            if !timings.is_empty() {
+                div { class: "flex flex-wrap gap-4 text-xs font-mono text-neutral-content/40",
+                    for (name, secs) in timings {
+                        span { "{name} {secs}" }
+                    }
+                }
+            }
+            if !report.events.is_empty() {
+                table { class: "w-full text-xs font-mono",
+                    tbody {
+                        for event in report.events.iter() {
+                            tr { class: "border-b border-neutral-content/5",
+                                td {
+                                    class: if event.status == "failure" { "py-1 pr-3 text-error" } else { "py-1 pr-3 text-neutral-content/60" },
+                                    "{event.status}"
+                                }
+                                td { class: "py-1 pr-3 text-neutral-content/70", "{event.resource}" }
+                                td { class: "py-1 text-neutral-content/50 break-all", "{event.message}" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 // --- Checkins ---
 
 #[component]
 fn Checkins() -> Element {
     let mut search_query = use_signal(|| String::new());
-    let checkin_data = use_resource(move || async move { get_all_checkin_logs().await.ok() });
+    let today = Local::now().date_naive();
+    let mut start = use_signal(|| (today - chrono::Duration::days(7)).format("%Y-%m-%d").to_string());
+    let mut end = use_signal(|| today.format("%Y-%m-%d").to_string());
+    let checkin_data = use_resource(move || async move { get_checkin_logs(start(), end()).await.ok() });
 
     let filtered_logs = use_memo(move || {
         let query = search_query().to_lowercase();
         let binding = checkin_data.read();
         let Some(Some(logs)) = binding.as_ref() else { return Vec::new() };
 
-        let mut filtered: Vec<_> = if query.is_empty() {
+        // Already newest first from the server
+        if query.is_empty() {
             logs.clone()
         } else {
             logs.iter()
                 .filter(|l| l.log.to_lowercase().contains(&query) || l.hostname.to_lowercase().contains(&query))
                 .cloned()
                 .collect()
-        };
-
-        filtered.sort_by(|a, b| {
-            let at = a.log.split(" - ").next().unwrap_or("");
-            let bt = b.log.split(" - ").next().unwrap_or("");
-            bt.cmp(at)
-        });
-        filtered
+        }
     });
 
+    let date_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm focus:outline-none focus:border-neutral-content/30";
+
     rsx! {
         Page {
             title: "Laptop Checkins",
-            nav_label: "Home",
-            nav_to: Route::Home {},
             div { class: "mb-6",
                 input {
                     class: "w-full px-4 py-3 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content placeholder-neutral-content/40 focus:outline-none focus:border-neutral-content/30 transition-colors",
@@ -316,26 +777,27 @@ fn Checkins() -> Element {
                     oninput: move |evt| search_query.set(evt.value().clone())
                 }
             }
+            div { class: "flex items-center gap-4 mb-6 text-xs text-neutral-content/50 uppercase tracking-wider",
+                label { class: "flex items-center gap-2", "From",
+                    input { r#type: "date", class: date_class, value: "{start}", oninput: move |e| start.set(e.value()) }
+                }
+                label { class: "flex items-center gap-2", "To",
+                    input { r#type: "date", class: date_class, value: "{end}", oninput: move |e| end.set(e.value()) }
+                }
+            }
             match &*checkin_data.read_unchecked() {
-                Some(Some(_)) => rsx! {
+                Some(Some(logs)) => rsx! {
+                    if logs.len() == CHECKIN_PAGE_SIZE {
+                        p { class: "mb-4 text-xs text-neutral-content/50",
+                            "Showing the newest {CHECKIN_PAGE_SIZE} checkins in this range"
+                        }
+                    }
                     div { class: "space-y-2",
                         if filtered_logs.read().is_empty() {
                             EmptyState { message: "No matching logs found" }
                         } else {
                             for log in filtered_logs.read().iter() {
-                                Link {
-                                    to: Route::CheckinLog {
-                                        hostname: log.hostname.clone(),
-                                        log_text: encode(&log.log).to_string(),
-                                    },
-                                    div {
-                                        class: "px-4 py-3 bg-neutral-content/5 border border-neutral-content/10 rounded-lg hover:bg-neutral-content/10 transition-colors cursor-pointer",
-                                        div { class: "mb-2",
-                                            span { class: "text-xs font-mono text-neutral-content/50", "{log.hostname}" }
-                                        }
-                                        pre { class: "text-sm text-neutral-content/70 font-mono whitespace-pre-wrap break-words", "{log.log}" }
-                                    }
-                                }
+                                CheckinCard { log: log.clone() }
                             }
                         }
                     }
@@ -347,26 +809,44 @@ fn Checkins() -> Element {
     }
 }
 
+/// A checkin in a list, linking to its own page.
 #[component]
-fn CheckinLog(hostname: String, log_text: String) -> Element {
-    let decoded = decode(&log_text).unwrap_or_default().to_string();
+fn CheckinCard(log: CheckinLogEntry) -> Element {
+    rsx! {
+        Link {
+            to: Route::CheckinLog { id: log.id.clone() },
+            div {
+                class: "px-4 py-3 bg-neutral-content/5 border border-neutral-content/10 rounded-lg hover:bg-neutral-content/10 transition-colors cursor-pointer",
+                div { class: "mb-2 flex gap-4",
+                    span { class: "text-xs font-mono text-neutral-content/50", "{log.hostname}" }
+                    span { class: "text-xs font-mono text-neutral-content/40", "{log.time}" }
+                }
+                pre { class: "text-sm text-neutral-content/70 font-mono whitespace-pre-wrap break-words", "{log.log}" }
+            }
+        }
+    }
+}
 
+#[component]
+fn CheckinLog(id: String) -> Element {
     let log_data = use_resource(move || {
-        let hostname = hostname.clone();
-        let log_text = decoded.clone();
-        async move { get_checkin_log(hostname, log_text).await.ok() }
+        let id = id.clone();
+        async move { get_checkin_log(id).await.ok() }
     });
 
     rsx! {
         Page {
             title: "Checkin Log",
-            nav_label: "Back to Checkins",
-            nav_to: Route::Checkins {},
             match &*log_data.read_unchecked() {
                 Some(Some(Some(log))) => rsx! {
                     div { class: "px-4 py-3 bg-neutral-content/5 border border-neutral-content/10 rounded-lg",
-                        div { class: "mb-4 pb-3 border-b border-neutral-content/10",
-                            span { class: "text-sm font-mono text-neutral-content/70", "{log.hostname}" }
+                        div { class: "mb-4 pb-3 border-b border-neutral-content/10 flex gap-6",
+                            Link {
+                                to: Route::Host { hostname: log.hostname.clone() },
+                                class: "text-sm font-mono text-neutral-content/70 hover:text-neutral-content",
+                                "{log.hostname}"
+                            }
+                            span { class: "text-sm font-mono text-neutral-content/50", "{log.time}" }
                         }
                         pre { class: "text-base text-neutral-content font-mono whitespace-pre-wrap break-words leading-relaxed", "{log.log}" }
                     }
@@ -377,3 +857,965 @@ fn CheckinLog(hostname: String, log_text: String) -> Element {
         }
     }
 }
+
+// --- Attendance ---
+
+#[component]
+fn Attendance() -> Element {
+    let today = Local::now().date_naive();
+    let mut start = use_signal(|| (today - chrono::Duration::days(30)).format("%Y-%m-%d").to_string());
+    let mut end = use_signal(|| today.format("%Y-%m-%d").to_string());
+    let report = use_resource(move || async move { get_attendance(start(), end()).await });
+
+    let input_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm focus:outline-none focus:border-neutral-content/30";
+    let heading = "text-left py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider";
+
+    rsx! {
+        Page {
+            title: "Attendance",
+            div { class: "flex items-center gap-4 mb-6 text-xs text-neutral-content/50 uppercase tracking-wider",
+                label { class: "flex items-center gap-2", "From",
+                    input { r#type: "date", class: input_class, value: "{start}", oninput: move |e| start.set(e.value()) }
+                }
+                label { class: "flex items-center gap-2", "To",
+                    input { r#type: "date", class: input_class, value: "{end}", oninput: move |e| end.set(e.value()) }
+                }
+                a {
+                    class: "ml-auto hover:text-neutral-content transition-colors",
+                    href: format!("/export/attendance.csv?start={}&end={}", start(), end()),
+                    "Download CSV"
+                }
+            }
+            match &*report.read_unchecked() {
+                Some(Ok(report)) if !report.students.is_empty() => rsx! {
+                    div { class: "flex gap-8 mb-6 text-xs text-neutral-content/50 uppercase tracking-wider",
+                        span { "Meeting days ", span { class: "text-neutral-content", "{report.meeting_days}" } }
+                        span { "Students ", span { class: "text-neutral-content", {report.students.len().to_string()} } }
+                        span { "Total hours ", span { class: "text-neutral-content", {format!("{:.1}", report.total_hours)} } }
+                    }
+                    table { class: "w-full border-collapse",
+                        thead {
+                            tr { class: "border-b border-neutral-content/10",
+                                th { class: heading, "Student" }
+                                th { class: heading, "ID" }
+                                th { class: heading, "Days" }
+                                th { class: heading, "Hours" }
+                            }
+                        }
+                        tbody {
+                            for student in report.students.iter() {
+                                tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors text-sm text-neutral-content/70",
+                                    td { class: "py-3 px-4", {student.name.clone().unwrap_or_else(|| "Unknown".to_string())} }
+                                    td { class: "py-3 px-4 font-mono text-neutral-content/50", "{student.student_id}" }
+                                    td { class: "py-3 px-4", {format!("{} / {}", student.days_attended, report.meeting_days)} }
+                                    td { class: "py-3 px-4", {format!("{:.1}", student.hours)} }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Ok(_)) => rsx! { EmptyState { message: "No checkins in this range" } },
+                Some(Err(e)) => rsx! { EmptyState { message: e.to_string() } },
+                None => rsx! { EmptyState { message: "Loading..." } },
+            }
+        }
+    }
+}
+
+// --- Roster ---
+
+#[component]
+fn Roster() -> Element {
+    let mut people = use_resource(move || async move { list_people().await });
+    let mut new_name = use_signal(String::new);
+    let mut new_id = use_signal(String::new);
+    let mut import_text = use_signal(String::new);
+    let mut message = use_signal(|| None::<String>);
+
+    let input_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm focus:outline-none focus:border-neutral-content/30";
+    let button_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+    let heading = "text-left py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider";
+
+    let add = move |_| async move {
+        match add_person(new_id(), new_name()).await {
+            Ok(person) => {
+                message.set(Some(format!("Added {} with ID {}", person.name, person.id)));
+                new_name.set(String::new());
+                new_id.set(String::new());
+                people.restart();
+            }
+            Err(e) => message.set(Some(e.to_string())),
+        }
+    };
+    let import = move |_| async move {
+        match import_people(import_text()).await {
+            Ok(summary) => {
+                message.set(Some(format!("Imported {} new, updated {}", summary.added, summary.updated)));
+                import_text.set(String::new());
+                people.restart();
+            }
+            Err(e) => message.set(Some(e.to_string())),
+        }
+    };
+
+    rsx! {
+        Page {
+            title: "Roster",
+            div { class: "flex items-center gap-4 mb-6",
+                input { class: input_class, placeholder: "Name", value: "{new_name}", oninput: move |e| new_name.set(e.value()) }
+                input { class: "{input_class} w-32 font-mono", placeholder: "ID (optional)", value: "{new_id}", oninput: move |e| new_id.set(e.value()) }
+                button { class: button_class, onclick: add, "Add" }
+                a { class: "ml-auto {button_class}", href: "/export/people.csv", "Download CSV" }
+            }
+            if let Some(message) = message() {
+                div { class: "mb-6 text-sm text-neutral-content/70", "{message}" }
+            }
+            match &*people.read_unchecked() {
+                Some(Ok(list)) if !list.is_empty() => rsx! {
+                    table { class: "w-full border-collapse mb-8",
+                        thead {
+                            tr { class: "border-b border-neutral-content/10",
+                                th { class: heading, "Name" }
+                                th { class: heading, "ID" }
+                                th { class: heading, "Active" }
+                                th { class: heading }
+                            }
+                        }
+                        tbody {
+                            for person in list.iter() {
+                                PersonRow {
+                                    key: "{person.id}",
+                                    person: person.clone(),
+                                    on_change: move |_| people.restart(),
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Ok(_)) => rsx! { EmptyState { message: "No people yet" } },
+                Some(Err(e)) => rsx! { EmptyState { message: e.to_string() } },
+                None => rsx! { EmptyState { message: "Loading..." } },
+            }
+            div { class: "flex flex-col gap-3",
+                span { class: "text-xs text-neutral-content/50 uppercase tracking-wider", "Import CSV (id,name,active)" }
+                textarea {
+                    class: "{input_class} font-mono h-32",
+                    placeholder: "id,name,active\n123456,Jane Doe,true\n,New Student,true",
+                    value: "{import_text}",
+                    oninput: move |e| import_text.set(e.value()),
+                }
+                button { class: "self-start {button_class}", onclick: import, "Import" }
+            }
+        }
+    }
+}
+
+/// One roster row; the name saves when the field loses focus.
+#[component]
+fn PersonRow(person: Person, on_change: EventHandler<()>) -> Element {
+    let mut name = use_signal(|| person.name.clone());
+    let mut error = use_signal(|| None::<String>);
+
+    let id = person.id.clone();
+    let active = person.active;
+    let save = move |active: bool| {
+        let id = id.clone();
+        async move {
+            match update_person(id, name(), active).await {
+                Ok(()) => {
+                    error.set(None);
+                    on_change.call(());
+                }
+                Err(e) => error.set(Some(e.to_string())),
+            }
+        }
+    };
+    let save_name = save.clone();
+    let original = person.name.clone();
+    let id = person.id.clone();
+    let regenerate = move |_| {
+        let id = id.clone();
+        async move {
+            match regenerate_person_id(id).await {
+                Ok(_) => on_change.call(()),
+                Err(e) => error.set(Some(e.to_string())),
+            }
+        }
+    };
+
+    let row_class = if active { "text-neutral-content/70" } else { "text-neutral-content/30" };
+    let button_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+
+    rsx! {
+        tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors text-sm {row_class}",
+            td { class: "py-2 px-4",
+                input {
+                    class: "w-full bg-transparent focus:outline-none",
+                    value: "{name}",
+                    oninput: move |e| name.set(e.value()),
+                    onblur: move |_| {
+                        if name() != original {
+                            spawn(save_name(active));
+                        }
+                    },
+                }
+                if let Some(error) = error() {
+                    div { class: "text-xs text-error", "{error}" }
+                }
+            }
+            td { class: "py-2 px-4 font-mono",
+                "{person.id}"
+                if !person.former_ids.is_empty() {
+                    span { class: "ml-2 text-xs text-neutral-content/30", {format!("was {}", person.former_ids.join(", "))} }
+                }
+            }
+            td { class: "py-2 px-4",
+                input { r#type: "checkbox", checked: active, onchange: move |_| save(!active) }
+            }
+            td { class: "py-2 px-4 text-right",
+                button { class: button_class, onclick: regenerate, "New ID" }
+            }
+        }
+    }
+}
+
+// --- Devices ---
+
+#[component]
+fn Devices() -> Element {
+    let mut devices = use_resource(move || async move { list_devices().await });
+    let mut new_hostname = use_signal(String::new);
+    // The last token issued, shown until the page is left since it can't be retrieved again.
+    let mut issued = use_signal(|| None::<(String, String)>);
+    let mut message = use_signal(|| None::<String>);
+
+    let input_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm focus:outline-none focus:border-neutral-content/30";
+    let button_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+    let heading = "text-left py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider";
+
+    let issue = move |hostname: String| {
+        spawn(async move {
+            match issue_device_token(hostname.clone()).await {
+                Ok(token) => {
+                    issued.set(Some((hostname, token)));
+                    message.set(None);
+                    devices.restart();
+                }
+                Err(e) => message.set(Some(e.to_string())),
+            }
+        });
+    };
+    let revoke = move |hostname: String| {
+        spawn(async move {
+            match revoke_device_token(hostname).await {
+                Ok(()) => devices.restart(),
+                Err(e) => message.set(Some(e.to_string())),
+            }
+        });
+    };
+
+    rsx! {
+        Page {
+            title: "Devices",
+            div { class: "flex items-center gap-4 mb-6",
+                input { class: input_class, placeholder: "Hostname", value: "{new_hostname}", oninput: move |e| new_hostname.set(e.value()) }
+                button {
+                    class: button_class,
+                    onclick: move |_| {
+                        issue(new_hostname());
+                        new_hostname.set(String::new());
+                    },
+                    "Issue token"
+                }
+            }
+            if let Some((hostname, token)) = issued() {
+                div { class: "mb-6 p-4 border border-neutral-content/10 rounded-lg text-sm text-neutral-content/70",
+                    div { class: "mb-2", "Token for {hostname}. Put it in the client's settings.toml; it won't be shown again." }
+                    code { class: "font-mono text-neutral-content select-all", "device_token = \"{token}\"" }
+                }
+            }
+            if let Some(message) = message() {
+                div { class: "mb-6 text-sm text-error", "{message}" }
+            }
+            match &*devices.read_unchecked() {
+                Some(Ok(list)) if !list.is_empty() => rsx! {
+                    table { class: "w-full border-collapse",
+                        thead {
+                            tr { class: "border-b border-neutral-content/10",
+                                th { class: heading, "Host" }
+                                th { class: heading, "Last seen" }
+                                th { class: heading, "Token issued" }
+                                th { class: heading }
+                            }
+                        }
+                        tbody {
+                            for device in list.iter().cloned() {
+                                tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors text-sm text-neutral-content/70",
+                                    td { class: "py-2 px-4 font-mono", "{device.hostname}" }
+                                    td { class: "py-2 px-4 text-neutral-content/50",
+                                        {device.last_seen.as_deref().map(format_timestamp).unwrap_or_else(|| "Never".to_string())}
+                                    }
+                                    td { class: "py-2 px-4 text-neutral-content/50",
+                                        {device.token_issued
+                                            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                                            .map(|t| t.with_timezone(&Local).format("%-m-%-d-%y").to_string())
+                                            .unwrap_or_else(|| "No token".to_string())}
+                                    }
+                                    td { class: "py-2 px-4 text-right space-x-4",
+                                        button {
+                                            class: button_class,
+                                            onclick: {
+                                                let hostname = device.hostname.clone();
+                                                move |_| issue(hostname.clone())
+                                            },
+                                            if device.token_issued.is_some() { "Replace token" } else { "Issue token" }
+                                        }
+                                        if device.token_issued.is_some() {
+                                            button {
+                                                class: button_class,
+                                                onclick: {
+                                                    let hostname = device.hostname.clone();
+                                                    move |_| revoke(hostname.clone())
+                                                },
+                                                "Revoke"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Ok(_)) => rsx! { EmptyState { message: "No devices yet" } },
+                Some(Err(e)) => rsx! { EmptyState { message: e.to_string() } },
+                None => rsx! { EmptyState { message: "Loading..." } },
+            }
+        }
+    }
+}
+
+// --- Groups ---
+
+#[component]
+fn Groups() -> Element {
+    let mut overview = use_resource(move || async move { list_groups().await });
+    let mut name = use_signal(String::new);
+    let mut environment = use_signal(String::new);
+    let mut patterns = use_signal(String::new);
+    let mut message = use_signal(|| None::<String>);
+
+    let input_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm focus:outline-none focus:border-neutral-content/30";
+    let select_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm";
+    let button_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+    let heading = "text-left py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider";
+
+    let save = move |_| async move {
+        match save_group(name(), environment(), patterns()).await {
+            Ok(()) => {
+                message.set(Some(format!("Saved {}", name())));
+                name.set(String::new());
+                environment.set(String::new());
+                patterns.set(String::new());
+                overview.restart();
+            }
+            Err(e) => message.set(Some(e.to_string())),
+        }
+    };
+    let remove = move |group: String| {
+        spawn(async move {
+            match delete_group(group).await {
+                Ok(()) => overview.restart(),
+                Err(e) => message.set(Some(e.to_string())),
+            }
+        });
+    };
+    let assign = move |hostname: String, group: String| {
+        spawn(async move {
+            match assign_host(hostname, group).await {
+                Ok(()) => overview.restart(),
+                Err(e) => message.set(Some(e.to_string())),
+            }
+        });
+    };
+
+    rsx! {
+        Page {
+            title: "Groups",
+            div { class: "flex items-center gap-4 mb-6",
+                input { class: input_class, placeholder: "Group", value: "{name}", oninput: move |e| name.set(e.value()) }
+                input { class: input_class, placeholder: "Environment", value: "{environment}", oninput: move |e| environment.set(e.value()) }
+                input { class: "{input_class} flex-1 font-mono", placeholder: "Hostname patterns, e.g. CAD-*", value: "{patterns}", oninput: move |e| patterns.set(e.value()) }
+                button { class: button_class, onclick: save, "Save" }
+            }
+            if let Some(message) = message() {
+                div { class: "mb-6 text-sm text-neutral-content/70", "{message}" }
+            }
+            match &*overview.read_unchecked() {
+                Some(Ok(data)) => rsx! {
+                    if data.groups.is_empty() {
+                        EmptyState { message: "No groups yet; every host gets the default bundle" }
+                    } else {
+                        table { class: "w-full border-collapse mb-8",
+                            thead {
+                                tr { class: "border-b border-neutral-content/10",
+                                    th { class: heading, "Group" }
+                                    th { class: heading, "Environment" }
+                                    th { class: heading, "Patterns" }
+                                    th { class: heading }
+                                }
+                            }
+                            tbody {
+                                for group in data.groups.iter().cloned() {
+                                    tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors text-sm text-neutral-content/70",
+                                        td { class: "py-2 px-4", "{group.name}" }
+                                        td { class: "py-2 px-4 font-mono", "{group.environment}" }
+                                        td { class: "py-2 px-4 font-mono text-neutral-content/50", {group.patterns.join(", ")} }
+                                        td { class: "py-2 px-4 text-right space-x-4",
+                                            button {
+                                                class: button_class,
+                                                onclick: {
+                                                    let group = group.clone();
+                                                    move |_| {
+                                                        name.set(group.name.clone());
+                                                        environment.set(group.environment.clone());
+                                                        patterns.set(group.patterns.join(", "));
+                                                    }
+                                                },
+                                                "Edit"
+                                            }
+                                            button {
+                                                class: button_class,
+                                                onclick: {
+                                                    let name = group.name.clone();
+                                                    move |_| remove(name.clone())
+                                                },
+                                                "Delete"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if !data.hosts.is_empty() {
+                        table { class: "w-full border-collapse",
+                            thead {
+                                tr { class: "border-b border-neutral-content/10",
+                                    th { class: heading, "Host" }
+                                    th { class: heading, "Assigned to" }
+                                    th { class: heading, "Gets" }
+                                }
+                            }
+                            tbody {
+                                for host in data.hosts.iter().cloned() {
+                                    tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors text-sm text-neutral-content/70",
+                                        td { class: "py-2 px-4 font-mono", "{host.hostname}" }
+                                        td { class: "py-2 px-4",
+                                            select {
+                                                class: select_class,
+                                                onchange: {
+                                                    let hostname = host.hostname.clone();
+                                                    move |e: Event<FormData>| assign(hostname.clone(), e.value())
+                                                },
+                                                option { value: "", selected: !host.explicit, "By pattern" }
+                                                for group in data.groups.iter() {
+                                                    option {
+                                                        value: "{group.name}",
+                                                        selected: host.explicit && host.group.as_ref() == Some(&group.name),
+                                                        "{group.name}"
+                                                    }
+                                                }
+                                            }
+                                        }
+                                        td { class: "py-2 px-4 text-neutral-content/50",
+                                            {host.group.clone().unwrap_or_else(|| "Default bundle".to_string())}
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Err(e)) => rsx! { EmptyState { message: e.to_string() } },
+                None => rsx! { EmptyState { message: "Loading..." } },
+            }
+        }
+    }
+}
+
+// --- Rollouts ---
+
+#[component]
+fn Rollouts() -> Element {
+    let mut overview = use_resource(move || async move { list_rollouts().await });
+    let mut message = use_signal(|| None::<String>);
+
+    let button_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+    let heading = "text-left py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider";
+
+    let promote = move |environment: Option<String>| {
+        spawn(async move {
+            match promote_rollout(environment).await {
+                Ok(()) => overview.restart(),
+                Err(e) => message.set(Some(e.to_string())),
+            }
+        });
+    };
+    let resume = move |environment: Option<String>| {
+        spawn(async move {
+            match resume_rollout(environment).await {
+                Ok(()) => overview.restart(),
+                Err(e) => message.set(Some(e.to_string())),
+            }
+        });
+    };
+
+    rsx! {
+        Page {
+            title: "Rollouts",
+            if let Some(message) = message() {
+                div { class: "mb-6 text-sm text-error", "{message}" }
+            }
+            match &*overview.read_unchecked() {
+                Some(Ok(data)) => rsx! {
+                    div { class: "mb-6 text-xs text-neutral-content/50 uppercase tracking-wider",
+                        if data.canaries.is_empty() {
+                            "No canaries configured; new commits go to every host at once"
+                        } else {
+                            "Canaries ", span { class: "text-neutral-content font-mono normal-case", {data.canaries.join(", ")} }
+                            ", promoted once {data.promote_after} of them report clean"
+                        }
+                    }
+                    if data.rollouts.is_empty() {
+                        EmptyState { message: "Nothing served from git yet" }
+                    } else {
+                        table { class: "w-full border-collapse",
+                            thead {
+                                tr { class: "border-b border-neutral-content/10",
+                                    th { class: heading, "Environment" }
+                                    th { class: heading, "Stable" }
+                                    th { class: heading, "Rolling out" }
+                                    th { class: heading, "Status" }
+                                    th { class: heading }
+                                }
+                            }
+                            tbody {
+                                for info in data.rollouts.iter().cloned() {
+                                    tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors text-sm text-neutral-content/70",
+                                        td { class: "py-2 px-4", {info.environment.clone().unwrap_or_else(|| "default".to_string())} }
+                                        td { class: "py-2 px-4 font-mono", {info.rollout.stable.as_deref().map(short_commit).unwrap_or_default()} }
+                                        td { class: "py-2 px-4 font-mono", {info.rollout.candidate.as_deref().map(short_commit).unwrap_or_default()} }
+                                        td { class: "py-2 px-4",
+                                            if info.rollout.candidate.is_none() {
+                                                span { class: "text-neutral-content/50", "Up to date" }
+                                            } else if info.rollout.halted {
+                                                span { class: "text-error", "Halted: a canary failed" }
+                                            } else {
+                                                span { class: "text-warning", "On canaries, {info.rollout.passed.len()}/{info.needed} passed" }
+                                            }
+                                        }
+                                        td { class: "py-2 px-4 text-right space-x-4",
+                                            if info.rollout.candidate.is_some() {
+                                                if info.rollout.halted {
+                                                    button {
+                                                        class: button_class,
+                                                        onclick: {
+                                                            let environment = info.environment.clone();
+                                                            move |_| resume(environment.clone())
+                                                        },
+                                                        "Retry canaries"
+                                                    }
+                                                }
+                                                button {
+                                                    class: button_class,
+                                                    onclick: {
+                                                        let environment = info.environment.clone();
+                                                        move |_| promote(environment.clone())
+                                                    },
+                                                    "Promote now"
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Err(e)) => rsx! { EmptyState { message: e.to_string() } },
+                None => rsx! { EmptyState { message: "Loading..." } },
+            }
+        }
+    }
+}
+
+// --- Users ---
+
+fn role_from_str(value: &str) -> Role {
+    Role::ALL.into_iter().find(|r| format!("{:?}", r) == value).unwrap_or(Role::Viewer)
+}
+
+#[component]
+fn Users() -> Element {
+    let mut users = use_resource(move || async move { list_users().await });
+    let mut username = use_signal(String::new);
+    let mut password = use_signal(String::new);
+    let mut role = use_signal(|| Role::Viewer);
+    let mut message = use_signal(|| None::<String>);
+
+    let input_class = "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm focus:outline-none focus:border-neutral-content/30";
+    let button_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+    let heading = "text-left py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider";
+
+    let add = move |_| async move {
+        match add_user(username(), password(), role()).await {
+            Ok(()) => {
+                message.set(Some(format!("Added {}", username())));
+                username.set(String::new());
+                password.set(String::new());
+                users.restart();
+            }
+            Err(e) => message.set(Some(e.to_string())),
+        }
+    };
+
+    rsx! {
+        Page {
+            title: "Users",
+            div { class: "flex items-center gap-4 mb-6",
+                input { class: input_class, placeholder: "Username", value: "{username}", oninput: move |e| username.set(e.value()) }
+                input { class: input_class, r#type: "password", placeholder: "Password", autocomplete: "new-password", value: "{password}", oninput: move |e| password.set(e.value()) }
+                RoleSelect { role: role(), on_change: move |r| role.set(r) }
+                button { class: button_class, onclick: add, "Add" }
+            }
+            if let Some(message) = message() {
+                div { class: "mb-6 text-sm text-neutral-content/70", "{message}" }
+            }
+            match &*users.read_unchecked() {
+                Some(Ok(list)) => rsx! {
+                    table { class: "w-full border-collapse",
+                        thead {
+                            tr { class: "border-b border-neutral-content/10",
+                                th { class: heading, "User" }
+                                th { class: heading, "Role" }
+                                th { class: heading, "Created" }
+                                th { class: heading, "Password" }
+                                th { class: heading }
+                            }
+                        }
+                        tbody {
+                            for user in list.iter() {
+                                UserRow {
+                                    key: "{user.username}",
+                                    user: user.clone(),
+                                    on_change: move |_| users.restart(),
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Err(e)) => rsx! { EmptyState { message: e.to_string() } },
+                None => rsx! { EmptyState { message: "Loading..." } },
+            }
+        }
+    }
+}
+
+#[component]
+fn RoleSelect(role: Role, on_change: EventHandler<Role>) -> Element {
+    rsx! {
+        select {
+            class: "px-3 py-2 bg-neutral-content/5 border border-neutral-content/10 rounded-lg text-neutral-content text-sm",
+            value: format!("{:?}", role),
+            onchange: move |e| on_change.call(role_from_str(&e.value())),
+            for r in Role::ALL {
+                option { value: format!("{:?}", r), selected: r == role, {r.label()} }
+            }
+        }
+    }
+}
+
+#[component]
+fn UserRow(user: UserInfo, on_change: EventHandler<()>) -> Element {
+    let mut password = use_signal(String::new);
+    let mut error = use_signal(|| None::<String>);
+
+    let username = user.username.clone();
+    let change_role = move |role: Role| {
+        let username = username.clone();
+        spawn(async move {
+            match set_user_role(username, role).await {
+                Ok(()) => {
+                    error.set(None);
+                    on_change.call(());
+                }
+                Err(e) => error.set(Some(e.to_string())),
+            }
+        });
+    };
+    let username = user.username.clone();
+    let reset = move |_| {
+        let username = username.clone();
+        async move {
+            match set_user_password(username, password()).await {
+                Ok(()) => {
+                    password.set(String::new());
+                    error.set(Some("Password changed".to_string()));
+                }
+                Err(e) => error.set(Some(e.to_string())),
+            }
+        }
+    };
+    let username = user.username.clone();
+    let remove = move |_| {
+        let username = username.clone();
+        async move {
+            match delete_user(username).await {
+                Ok(()) => on_change.call(()),
+                Err(e) => error.set(Some(e.to_string())),
+            }
+        }
+    };
+
+    let created = chrono::DateTime::from_timestamp(user.created, 0)
+        .map(|t| t.with_timezone(&Local).format("%-m-%-d-%y").to_string())
+        .unwrap_or_default();
+    let button_class = "text-xs text-neutral-content/60 hover:text-neutral-content transition-colors uppercase tracking-wider";
+
+    rsx! {
+        tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors text-sm text-neutral-content/70",
+            td { class: "py-2 px-4",
+                "{user.username}"
+                if let Some(error) = error() {
+                    div { class: "text-xs text-neutral-content/50", "{error}" }
+                }
+            }
+            td { class: "py-2 px-4", RoleSelect { role: user.role, on_change: change_role } }
+            td { class: "py-2 px-4 text-neutral-content/50", "{created}" }
+            td { class: "py-2 px-4",
+                div { class: "flex items-center gap-3",
+                    input {
+                        class: "w-40 bg-transparent border-b border-neutral-content/10 focus:outline-none",
+                        r#type: "password",
+                        placeholder: "New password",
+                        autocomplete: "new-password",
+                        value: "{password}",
+                        oninput: move |e| password.set(e.value()),
+                    }
+                    button { class: button_class, onclick: reset, "Set" }
+                }
+            }
+            td { class: "py-2 px-4 text-right",
+                button { class: button_class, onclick: remove, "Delete" }
+            }
+        }
+    }
+}
+
+// --- Inventory ---
+
+#[component]
+fn Inventory() -> Element {
+    let inventory = use_resource(move || async move { get_inventory().await.ok() });
+
+    rsx! {
+        Page {
+            title: "Inventory",
+            match &*inventory.read_unchecked() {
+                Some(Some(hosts)) if !hosts.is_empty() => rsx! {
+                    div { class: "overflow-x-auto",
+                        table { class: "w-full border-collapse",
+                            thead {
+                                tr { class: "border-b border-neutral-content/10",
+                                    for heading in ["Host", "OS", "CPU", "RAM", "Disk free", "Battery", "Reported"] {
+                                        th { class: "text-left py-3 px-4 text-xs font-light text-neutral-content/50 uppercase tracking-wider", "{heading}" }
+                                    }
+                                }
+                            }
+                            tbody {
+                                for host in hosts.iter() {
+                                    tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors text-sm text-neutral-content/70",
+                                        td { class: "py-3 px-4 font-mono",
+                                            Link {
+                                                to: Route::Host { hostname: host.hostname.clone() },
+                                                class: "hover:text-neutral-content",
+                                                "{host.hostname}"
+                                            }
+                                        }
+                                        td { class: "py-3 px-4", {host.facts.os_version.clone().or(host.facts.os_name.clone()).unwrap_or_default()} }
+                                        td { class: "py-3 px-4", {host.facts.cpu_model.clone().unwrap_or_default()} }
+                                        td { class: "py-3 px-4", {host.facts.memory_total_bytes.map(format_bytes).unwrap_or_default()} }
+                                        td { class: "py-3 px-4", {host.facts.disk_free_bytes.map(format_bytes).unwrap_or_default()} }
+                                        td { class: "py-3 px-4", {host.facts.battery_health_percent.map(|p| format!("{:.0}%", p)).unwrap_or_default()} }
+                                        td { class: "py-3 px-4 font-mono text-neutral-content/50", {format_timestamp(&host.timestamp)} }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(_) => rsx! { EmptyState { message: "No hosts have reported facts yet" } },
+                None => rsx! { EmptyState { message: "Loading..." } },
+            }
+        }
+    }
+}
+
+// --- Host ---
+
+#[component]
+fn Host(hostname: String) -> Element {
+    let title = format!("Host: {}", hostname);
+    let history = use_resource(move || {
+        let hostname = hostname.clone();
+        async move { get_host_history(hostname).await.ok() }
+    });
+
+    rsx! {
+        Page {
+            title,
+            match &*history.read_unchecked() {
+                Some(Some(history)) => rsx! { HostOverview { history: history.clone() } },
+                Some(None) => rsx! { EmptyState { message: "Failed to load host" } },
+                None => rsx! { EmptyState { message: "Loading..." } },
+            }
+        }
+    }
+}
+
+#[component]
+fn HostOverview(history: HostHistory) -> Element {
+    let last_seen = history.last_seen.as_deref().map(format_timestamp).unwrap_or_else(|| "Never".to_string());
+    let heading = "text-xs text-neutral-content/50 uppercase tracking-wider mb-3";
+
+    rsx! {
+        div { class: "flex gap-8 mb-8 text-xs text-neutral-content/50 uppercase tracking-wider",
+            span { "Last seen ", span { class: "text-neutral-content font-mono normal-case", "{last_seen}" } }
+            if let Some(commit) = &history.commit {
+                span { "Commit ", span { class: "text-neutral-content font-mono normal-case", title: "{commit}", {short_commit(commit)} } }
+            }
+        }
+
+        if !history.success_by_day.is_empty() {
+            h2 { class: heading, "Success rate" }
+            div { class: "flex items-end gap-2 h-24 mb-8",
+                for day in history.success_by_day.iter() {
+                    div {
+                        class: "flex-1 flex flex-col justify-end h-full",
+                        title: format!("{}: {}/{} runs succeeded", day.day, day.successes, day.runs),
+                        div {
+                            class: if day.successes == day.runs { "bg-success rounded-sm" } else { "bg-error rounded-sm" },
+                            style: format!("height: {}%", (day.successes * 100 / day.runs.max(1)).max(4)),
+                        }
+                    }
+                }
+            }
+        }
+
+        h2 { class: heading, "Recent runs" }
+        if history.runs.is_empty() {
+            EmptyState { message: "No runs reported" }
+        } else {
+            table { class: "w-full mb-8 text-sm",
+                tbody {
+                    for run in history.runs.iter() {
+                        tr { class: "border-b border-neutral-content/5 hover:bg-neutral-content/5 transition-colors",
+                            td { class: "py-2 pr-6 font-mono text-neutral-content/70",
+                                Link {
+                                    to: Route::Logs { interval: run.interval, hostname: history.hostname.clone() },
+                                    {format_timestamp(&run.timestamp)}
+                                }
+                            }
+                            td { class: "py-2 pr-6",
+                                span { class: "flex items-center gap-2 text-xs text-neutral-content/70",
+                                    div { class: format!("w-2 h-2 rounded-full {} {}", status_color(&run.status), offline_marker(run.offline)) }
+                                    "{run.status}"
+                                    if run.offline { " (offline)" }
+                                }
+                            }
+                            td { class: "py-2 pr-6 text-xs text-neutral-content/50", "Exit code: {run.exit_code}" }
+                            td { class: "py-2 text-xs font-mono text-neutral-content/50",
+                                {run.commit.as_deref().map(short_commit).unwrap_or_default()}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(checkins) = &history.checkins {
+            h2 { class: heading, "Recent checkins" }
+            if checkins.is_empty() {
+                EmptyState { message: "No checkins from this host" }
+            } else {
+                div { class: "space-y-2 mb-8",
+                    for log in checkins.iter() {
+                        CheckinCard { log: log.clone() }
+                    }
+                }
+            }
+        }
+
+        h2 { class: heading, "Facts" }
+        if let Some(inventory) = &history.inventory {
+            FactsTable { inventory: inventory.clone() }
+        } else {
+            EmptyState { message: "No facts reported for this host" }
+        }
+    }
+}
+
+/// All facts for one host, plus its network interfaces.
+#[component]
+fn FactsTable(inventory: HostInventory) -> Element {
+    let facts = &inventory.facts;
+    let disk = match (facts.disk_free_bytes, facts.disk_total_bytes) {
+        (Some(free), Some(total)) => Some(format!("{} free of {}", format_bytes(free), format_bytes(total))),
+        (Some(free), None) => Some(format!("{} free", format_bytes(free))),
+        _ => None,
+    };
+    let rows: Vec<(&str, String)> = [
+        ("OS", facts.os_name.clone()),
+        ("Version", facts.os_version.clone()),
+        ("Kernel", facts.kernel.clone()),
+        ("CPU", facts.cpu_model.clone()),
+        ("Cores", facts.cpu_count.map(|c| c.to_string())),
+        ("RAM", facts.memory_total_bytes.map(format_bytes)),
+        ("Disk", disk),
+        ("Serial", facts.serial_number.clone()),
+        ("Battery health", facts.battery_health_percent.map(|p| format!("{:.1}%", p))),
+        ("Uptime", facts.uptime_seconds.map(|s| format!("{}d {}h {}m", s / 86400, s / 3600 % 24, s / 60 % 60))),
+        ("Reported", Some(format_timestamp(&inventory.timestamp))),
+        ("Source", Some(facts.source.clone())),
+    ]
+    .into_iter()
+    .filter_map(|(label, value)| Some((label, value?)))
+    .collect();
+
+    rsx! {
+        table { class: "w-full mb-8 text-sm",
+            tbody {
+                for (label, value) in rows {
+                    tr { class: "border-b border-neutral-content/5",
+                        td { class: "py-2 pr-6 w-48 text-xs text-neutral-content/50 uppercase tracking-wider", "{label}" }
+                        td { class: "py-2 text-neutral-content/80 font-mono", "{value}" }
+                    }
+                }
+            }
+        }
+        if !facts.interfaces.is_empty() {
+            h2 { class: "text-xs text-neutral-content/50 uppercase tracking-wider mb-3", "Network interfaces" }
+            table { class: "w-full text-sm font-mono",
+                tbody {
+                    for iface in facts.interfaces.iter() {
+                        tr { class: "border-b border-neutral-content/5 text-neutral-content/70",
+                            td { class: "py-2 pr-6", "{iface.name}" }
+                            td { class: "py-2 pr-6", {iface.mac.clone().unwrap_or_default()} }
+                            td { class: "py-2", {iface.ips.join(", ")} }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
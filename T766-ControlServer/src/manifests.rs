@@ -1,21 +1,339 @@
-/// Serves puppet manifests as a tarball.
+/// Serves the puppet bundle (manifests, modules and hiera data) as a signed tarball.
+use axum::http::{HeaderMap, HeaderName, StatusCode, header};
 use axum::response::IntoResponse;
+use ed25519_dalek::{Signer, SigningKey};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use crate::config::CONFIG;
+use crate::devices::Device;
 
-pub async fn handler() -> impl IntoResponse {
-    match tokio::task::spawn_blocking(|| -> std::io::Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        let mut archive = tar::Builder::new(&mut buf);
-        archive.append_dir_all("manifests", "/puppet/manifests")?;
-        archive.finish()?;
-        drop(archive);
-        Ok(buf)
+const SIGNING_KEY_PATH: &str = "/opt/puppet/signing.key";
+
+/// Header carrying the hex-encoded ed25519 signature of the tarball, or of
+/// the body of `/pause`.
+pub const SIGNATURE_HEADER: &str = "x-manifest-signature";
+
+/// Bundle signing key, generated on first use if it doesn't exist yet.
+static SIGNING_KEY: Lazy<SigningKey> = Lazy::new(|| {
+    load_or_create_key(SIGNING_KEY_PATH).expect("Failed to load manifest signing key")
+});
+
+/// Read the hex-encoded key seed from disk, creating a new one if missing.
+fn load_or_create_key(path: &str) -> std::io::Result<SigningKey> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => {
+            let seed: [u8; 32] = hex::decode(contents.trim())
+                .ok()
+                .and_then(|b| b.try_into().ok())
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "signing key must be 32 hex-encoded bytes"))?;
+            Ok(SigningKey::from_bytes(&seed))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let key = SigningKey::generate(&mut rand_core::OsRng);
+            if let Some(parent) = std::path::Path::new(path).parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, hex::encode(key.to_bytes()))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+            }
+            println!("Generated new manifest signing key at {}", path);
+            Ok(key)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Hex-encoded signature of `bytes` for `SIGNATURE_HEADER`.
+pub fn sign(bytes: &[u8]) -> String {
+    hex::encode(SIGNING_KEY.sign(bytes).to_bytes())
+}
+
+/// Hex-encoded public key that clients pin as `manifest_public_key`.
+pub fn public_key_hex() -> String {
+    hex::encode(SIGNING_KEY.verifying_key().to_bytes())
+}
+
+/// Append everything under `src` as `prefix/...`, in sorted order with fixed
+/// metadata so unchanged content always produces a byte-identical tarball.
+fn append_sorted<W: Write>(archive: &mut tar::Builder<W>, prefix: &Path, src: &Path) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(src)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = prefix.join(entry.file_name());
+        if path.is_dir() {
+            archive.append_dir(&name, &path)?;
+            append_sorted(archive, &name, &path)?;
+        } else {
+            archive.append_path_with_name(&path, &name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Directory the bundle is built from.
+const PUPPET_DIR: &str = "/puppet";
+
+/// Where a group's environment lives on disk: `/puppet/environments/<name>`,
+/// laid out like `/puppet` itself. In `manifest_repo` it's the branch of that name.
+fn environment_dir(environment: Option<&str>) -> PathBuf {
+    match environment {
+        Some(env) => Path::new(PUPPET_DIR).join("environments").join(env),
+        None => PathBuf::from(PUPPET_DIR),
+    }
+}
+
+fn environment_ref(environment: Option<&str>) -> &str {
+    environment.unwrap_or(&CONFIG.manifest_ref)
+}
+
+/// Directories copied into the bundle. Only `manifests` has to exist.
+const BUNDLE_DIRS: [&str; 3] = ["manifests", "modules", "data"];
+
+/// Hiera config used when `/puppet/hiera.yaml` doesn't exist: per-host, then
+/// per-OS, then common data, all under `data/`.
+const DEFAULT_HIERA_CONFIG: &str = r#"---
+version: 5
+defaults:
+  datadir: data
+  data_hash: yaml_data
+hierarchy:
+  - name: "Per-host data"
+    path: "nodes/%{facts.networking.hostname}.yaml"
+  - name: "Per-OS data"
+    path: "os/%{facts.os.family}.yaml"
+  - name: "Common data"
+    path: "common.yaml"
+"#;
+
+/// What the bundle was built from, shipped in it as `bundle.json`.
+#[derive(serde::Serialize)]
+struct BundleInfo<'a> {
+    environment: Option<&'a str>,
+    commit: Option<&'a str>,
+    #[serde(rename = "ref")]
+    git_ref: Option<&'a str>,
+}
+
+/// Append a generated file with fixed metadata.
+fn append_file<W: Write>(archive: &mut tar::Builder<W>, name: &str, contents: &[u8]) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header.set_cksum();
+    archive.append_data(&mut header, name, contents)
+}
+
+/// Build the bundle tarball: manifests, modules, hiera data, `hiera.yaml` and
+/// `bundle.json`, from `manifest_repo` at `manifest_ref` or from `/puppet`.
+/// A group's `environment` replaces `manifest_ref` or `/puppet` with its own.
+/// From git, `hostname` decides whether a commit still in rollout is served.
+fn build_bundle(environment: Option<&str>, hostname: Option<&str>) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut archive = tar::Builder::new(&mut buf);
+    archive.mode(tar::HeaderMode::Deterministic);
+
+    let (has_hiera_config, commit) = match &CONFIG.manifest_repo {
+        Some(repo) => {
+            let commit = commit_for(repo, environment, hostname)?;
+            (append_git_tree(&mut archive, repo, &commit)?, Some(commit))
+        }
+        None => (append_puppet_dir(&mut archive, &environment_dir(environment))?, None),
+    };
+    if !has_hiera_config {
+        append_file(&mut archive, "hiera.yaml", DEFAULT_HIERA_CONFIG.as_bytes())?;
+    }
+
+    let info = BundleInfo {
+        environment,
+        commit: commit.as_deref(),
+        git_ref: CONFIG.manifest_repo.as_ref().map(|_| environment_ref(environment)),
+    };
+    append_file(&mut archive, "bundle.json", &serde_json::to_vec(&info)?)?;
+
+    archive.finish()?;
+    drop(archive);
+    Ok(buf)
+}
+
+/// Add the bundle directories and `hiera.yaml` from disk. Returns whether
+/// there was a `hiera.yaml`.
+fn append_puppet_dir<W: Write>(archive: &mut tar::Builder<W>, root: &Path) -> std::io::Result<bool> {
+    for dir in BUNDLE_DIRS {
+        let src = root.join(dir);
+        if dir != "manifests" && !src.is_dir() {
+            continue;
+        }
+        archive.append_dir(dir, &src)?;
+        append_sorted(archive, Path::new(dir), &src)?;
+    }
+
+    let hiera_config = root.join("hiera.yaml");
+    if !hiera_config.is_file() {
+        return Ok(false);
+    }
+    archive.append_path_with_name(&hiera_config, "hiera.yaml")?;
+    Ok(true)
+}
+
+/// Run git in `repo` and return its stdout.
+fn git(repo: &str, args: &[&str]) -> std::io::Result<Vec<u8>> {
+    let output = std::process::Command::new("git").arg("-C").arg(repo).args(args).output()?;
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn resolve_commit(repo: &str, git_ref: &str) -> std::io::Result<String> {
+    let out = git(repo, &["rev-parse", "--verify", &format!("{}^{{commit}}", git_ref)])?;
+    Ok(String::from_utf8_lossy(&out).trim().to_string())
+}
+
+/// Where `environment`'s ref points now, and its rollout after seeing that.
+fn rollout_state(repo: &str, environment: Option<&str>) -> std::io::Result<(String, crate::rollouts::Rollout)> {
+    let head = resolve_commit(repo, environment_ref(environment))?;
+    let rollout = crate::rollouts::observe(environment, &head).map_err(|e| Error::other(e.to_string()))?;
+    Ok((head, rollout))
+}
+
+/// The commit `hostname` gets from `environment`: where its ref points,
+/// unless a rollout is holding that back.
+fn commit_for(repo: &str, environment: Option<&str>, hostname: Option<&str>) -> std::io::Result<String> {
+    let (head, rollout) = rollout_state(repo, environment)?;
+    Ok(crate::rollouts::served_commit(&rollout, hostname).map(str::to_string).unwrap_or(head))
+}
+
+/// The commit each host would be served now, if serving from git. This is
+/// for the dashboard, so it only reads rollouts: a commit no host has been
+/// served yet shows up once `/manifests` has seen it.
+pub fn served_commits(hostnames: &[String]) -> Vec<Option<String>> {
+    use std::collections::HashMap;
+
+    let Some(repo) = &CONFIG.manifest_repo else { return vec![None; hostnames.len()] };
+    let groups = crate::db::iter_groups().unwrap_or_else(|e| {
+        println!("Failed to read groups: {}", e);
+        Vec::new()
+    });
+    let rollouts: HashMap<String, crate::rollouts::Rollout> = crate::db::iter_rollouts()
+        .unwrap_or_else(|e| {
+            println!("Failed to read rollouts: {}", e);
+            Vec::new()
+        })
+        .into_iter()
+        .collect();
+    let mut heads: HashMap<Option<&str>, Option<String>> = HashMap::new();
+    hostnames.iter()
+        .map(|hostname| {
+            let environment = crate::groups::group_for(&groups, hostname).map(|g| g.environment.as_str());
+            let head = heads.entry(environment)
+                .or_insert_with(|| resolve_commit(repo, environment_ref(environment))
+                    .map_err(|e| println!("Failed to resolve {}: {}", environment_ref(environment), e))
+                    .ok())
+                .as_ref()?;
+            let served = rollouts.get(crate::rollouts::key(environment))
+                .and_then(|rollout| crate::rollouts::served_commit(rollout, Some(hostname)));
+            Some(served.unwrap_or(head).to_string())
+        })
+        .collect()
+}
+
+/// Add the bundle directories and `hiera.yaml` as of `commit`. Returns
+/// whether the commit has a `hiera.yaml`. Entries keep git's metadata, which
+/// is fixed per commit, so a commit always produces the same tarball.
+fn append_git_tree<W: Write>(archive: &mut tar::Builder<W>, repo: &str, commit: &str) -> std::io::Result<bool> {
+    let listing = git(repo, &["ls-tree", "--name-only", commit])?;
+    let top_level: Vec<&str> = std::str::from_utf8(&listing)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?
+        .lines()
+        .collect();
+    if !top_level.contains(&"manifests") {
+        return Err(Error::new(ErrorKind::NotFound, format!("no manifests directory in {}", commit)));
+    }
+    let paths: Vec<&str> = BUNDLE_DIRS.iter()
+        .copied()
+        .chain(["hiera.yaml"])
+        .filter(|p| top_level.contains(p))
+        .collect();
+
+    let mut args = vec!["archive", "--format=tar", commit, "--"];
+    args.extend(&paths);
+    let tarball = git(repo, &args)?;
+    let mut source = tar::Archive::new(std::io::Cursor::new(tarball));
+    for entry in source.entries()? {
+        let mut entry = entry?;
+        // git archive records the commit ID in a global header; bundle.json carries it instead.
+        if entry.header().entry_type().is_pax_global_extensions() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        let mut header = entry.header().clone();
+        archive.append_data(&mut header, path, &mut entry)?;
+    }
+    Ok(paths.contains(&"hiera.yaml"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ManifestQuery {
+    /// Which host is asking, for clients without a device token. Anyone can
+    /// claim any hostname here, so it's ignored once tokens are required and
+    /// for hosts that have been issued one.
+    hostname: Option<String>,
+}
+
+/// Serve the bundle for the requesting host's group, or the default bundle
+/// if it isn't in one.
+pub async fn handler(
+    device: Option<axum::Extension<Device>>,
+    axum::extract::Query(query): axum::extract::Query<ManifestQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    // A token decides who the host is. The query is unauthenticated, so it's
+    // only used while tokens are optional, and never for a host that has one.
+    let hostname = device.map(|d| d.0.hostname).or_else(|| {
+        query.hostname.filter(|h| {
+            !CONFIG.require_device_tokens && !crate::devices::has_token(h).unwrap_or(true)
+        })
+    });
+    match tokio::task::spawn_blocking(move || -> std::io::Result<(Vec<u8>, String, String)> {
+        let environment = match &hostname {
+            Some(hostname) => crate::groups::environment_for(hostname).map_err(|e| Error::other(e.to_string()))?,
+            None => None,
+        };
+        let buf = build_bundle(environment.as_deref(), hostname.as_deref()).map_err(|e| {
+            println!("Failed to build bundle for {}: {}", environment.as_deref().unwrap_or("the default environment"), e);
+            e
+        })?;
+        let etag = format!("\"{:x}\"", Sha256::digest(&buf));
+        let signature = sign(&buf);
+        Ok((buf, etag, signature))
     }).await {
-        Ok(Ok(bytes)) => (
-            [(axum::http::header::CONTENT_TYPE, "application/x-tar")],
-            bytes,
-        ).into_response(),
+        Ok(Ok((bytes, etag, signature))) => {
+            let unchanged = headers.get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.split(',').any(|t| t.trim() == etag));
+            if unchanged {
+                return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+            }
+            (
+                [
+                    (header::CONTENT_TYPE, String::from("application/x-tar")),
+                    (header::ETAG, etag),
+                    (HeaderName::from_static(SIGNATURE_HEADER), signature),
+                ],
+                bytes,
+            ).into_response()
+        }
         _ => (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::INTERNAL_SERVER_ERROR,
             "Failed to build archive",
         ).into_response(),
     }
@@ -0,0 +1,93 @@
+/// Fleet-wide pause: while it's on, clients skip `puppet apply` and report
+/// "paused" instead, leaving every host exactly as it is.
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pause {
+    /// Unix time the pause lifts on its own, or `None` to hold until resumed.
+    pub until: Option<i64>,
+    pub reason: String,
+    /// Username that turned it on.
+    pub by: String,
+    pub since: i64,
+}
+
+/// What clients get from `/pause`, signed with the manifest signing key.
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PauseStatus {
+    pub paused: bool,
+    pub until: Option<i64>,
+    pub reason: Option<String>,
+    /// Unix time this answer was given, so an old one can't be replayed.
+    pub issued: i64,
+}
+
+/// The pause in effect, if any. An expired pause counts as none.
+#[cfg(feature = "server")]
+pub fn current() -> Result<Option<Pause>, ServerFnError> {
+    let now = chrono::Local::now().timestamp();
+    Ok(crate::db::get_pause()?.filter(|p| p.until.is_none_or(|until| until > now)))
+}
+
+/// `GET /pause`, checked by clients before every apply. Signed like bundles
+/// are, since a forged "not paused" would let applies through.
+#[cfg(feature = "server")]
+pub async fn status_handler() -> axum::response::Response {
+    use axum::http::{HeaderName, StatusCode, header};
+    use axum::response::IntoResponse;
+
+    let status = current().and_then(|pause| {
+        let status = PauseStatus {
+            paused: pause.is_some(),
+            until: pause.as_ref().and_then(|p| p.until),
+            reason: pause.map(|p| p.reason),
+            issued: chrono::Local::now().timestamp(),
+        };
+        serde_json::to_vec(&status).map_err(|e| ServerFnError::new(e.to_string()))
+    });
+    match status {
+        Ok(body) => (
+            [
+                (header::CONTENT_TYPE, "application/json".to_string()),
+                (HeaderName::from_static(crate::manifests::SIGNATURE_HEADER), crate::manifests::sign(&body)),
+            ],
+            body,
+        ).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[server]
+pub async fn get_pause() -> Result<Option<Pause>, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Viewer).await?;
+    current()
+}
+
+/// Pause applies on every host, for `hours` or until resumed.
+#[server]
+pub async fn pause_applies(hours: Option<u32>, reason: String) -> Result<(), ServerFnError> {
+    let user = crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let now = chrono::Local::now().timestamp();
+    let pause = Pause {
+        until: hours.filter(|h| *h > 0).map(|h| now + i64::from(h) * 3600),
+        reason: reason.trim().to_string(),
+        by: user.username,
+        since: now,
+    };
+    crate::db::set_pause(Some(&pause))?;
+    println!("{} paused applies{}", pause.by, match pause.until {
+        Some(_) => format!(" for {} hours", hours.unwrap_or_default()),
+        None => String::new(),
+    });
+    Ok(())
+}
+
+#[server]
+pub async fn resume_applies() -> Result<(), ServerFnError> {
+    let user = crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    crate::db::set_pause(None)?;
+    println!("{} resumed applies", user.username);
+    Ok(())
+}
@@ -0,0 +1,198 @@
+/// Student roster, stored in the database with CSV as an import/export format.
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// File the roster used to live in, imported once on first start.
+#[cfg(feature = "server")]
+pub const PEOPLE_CSV: &str = "/opt/puppet/people.csv";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Person {
+    /// The 6-digit code students type into the checkin app.
+    pub id: String,
+    pub name: String,
+    /// Alumni are kept so old checkins still show a name.
+    pub active: bool,
+    /// IDs this person had before theirs was regenerated.
+    #[serde(default)]
+    pub former_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+}
+
+#[cfg(feature = "server")]
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 6 && id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// A random 6-digit ID nobody has, or has had.
+#[cfg(feature = "server")]
+fn generate_id(people: &[Person]) -> String {
+    use rand_core::RngCore;
+    loop {
+        let id = format!("{:06}", rand_core::OsRng.next_u32() % 1_000_000);
+        if !people.iter().any(|p| p.id == id || p.former_ids.contains(&id)) {
+            return id;
+        }
+    }
+}
+
+/// Parse roster CSV: `id,name[,active]` with a header row. A blank ID gets one generated.
+#[cfg(feature = "server")]
+pub fn parse_csv(text: &str) -> Result<Vec<(String, String, bool)>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let mut rows = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| e.to_string())?;
+        let id = record.get(0).unwrap_or_default().to_string();
+        let name = record.get(1).unwrap_or_default().to_string();
+        if name.is_empty() {
+            continue;
+        }
+        if !id.is_empty() && !is_valid_id(&id) {
+            return Err(format!("Row {}: {:?} is not a 6-digit ID", line + 2, id));
+        }
+        let active = !matches!(record.get(2).map(str::to_lowercase).as_deref(), Some("false" | "no" | "0"));
+        rows.push((id, name, active));
+    }
+    Ok(rows)
+}
+
+/// Merge CSV rows into the roster: known IDs are updated, the rest added.
+#[cfg(feature = "server")]
+pub fn import(text: &str) -> Result<ImportSummary, String> {
+    let rows = parse_csv(text)?;
+    let mut people = crate::db::iter_people().map_err(|e| e.to_string())?;
+    let mut summary = ImportSummary { added: 0, updated: 0 };
+    let mut changed = Vec::new();
+
+    for (id, name, active) in rows {
+        // Past checkins under a former ID belong to its owner, so it can't be reused.
+        if let Some(owner) = people.iter().find(|p| p.former_ids.contains(&id)) {
+            return Err(format!("ID {} is already taken as a former ID of {}", id, owner.name));
+        }
+        if let Some(person) = people.iter_mut().find(|p| p.id == id) {
+            person.name = name;
+            person.active = active;
+            changed.push(person.clone());
+            summary.updated += 1;
+        } else {
+            let id = if id.is_empty() { generate_id(&people) } else { id };
+            let person = Person { id, name, active, former_ids: Vec::new() };
+            people.push(person.clone());
+            changed.push(person);
+            summary.added += 1;
+        }
+    }
+
+    crate::db::put_people(&changed).map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+/// The whole roster as CSV.
+#[cfg(feature = "server")]
+pub fn to_csv() -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["id", "name", "active"]).map_err(|e| e.to_string())?;
+    for person in list().map_err(|e| e.to_string())? {
+        writer.write_record([&person.id, &person.name, &person.active.to_string()])
+            .map_err(|e| e.to_string())?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// Import `people.csv` the first time the server starts with an empty roster.
+#[cfg(feature = "server")]
+pub fn import_legacy_csv() {
+    match crate::db::iter_people() {
+        Ok(people) if people.is_empty() => {}
+        Ok(_) => return,
+        Err(e) => {
+            println!("Failed to read roster: {}", e);
+            return;
+        }
+    }
+    let Ok(text) = std::fs::read_to_string(PEOPLE_CSV) else { return };
+    match import(&text) {
+        Ok(summary) => println!("Imported {} people from {}", summary.added, PEOPLE_CSV),
+        Err(e) => println!("Failed to import {}: {}", PEOPLE_CSV, e),
+    }
+}
+
+#[cfg(feature = "server")]
+fn list() -> Result<Vec<Person>, ServerFnError> {
+    let mut people = crate::db::iter_people()?;
+    people.sort_by_key(|p| (!p.active, p.name.to_lowercase()));
+    Ok(people)
+}
+
+/// Active people first, then by name.
+#[server]
+pub async fn list_people() -> Result<Vec<Person>, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    list()
+}
+
+/// Add a person, generating an ID if `id` is blank.
+#[server]
+pub async fn add_person(id: String, name: String) -> Result<Person, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::new("Name is required"));
+    }
+    let people = crate::db::iter_people()?;
+    let id = match id.trim() {
+        "" => generate_id(&people),
+        id if !is_valid_id(id) => return Err(ServerFnError::new("ID must be 6 digits")),
+        id if people.iter().any(|p| p.id == id || p.former_ids.iter().any(|f| f == id)) => {
+            return Err(ServerFnError::new(format!("ID {} is already taken", id)));
+        }
+        id => id.to_string(),
+    };
+    let person = Person { id, name, active: true, former_ids: Vec::new() };
+    crate::db::put_people(std::slice::from_ref(&person))?;
+    Ok(person)
+}
+
+/// Rename a person or change whether they're active.
+#[server]
+pub async fn update_person(id: String, name: String, active: bool) -> Result<(), ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::new("Name is required"));
+    }
+    let mut person = crate::db::get_person(&id)?
+        .ok_or_else(|| ServerFnError::new(format!("No person with ID {}", id)))?;
+    person.name = name;
+    person.active = active;
+    crate::db::put_people(&[person])
+}
+
+/// Give a person a new random ID, keeping the old one so past checkins still resolve.
+#[server]
+pub async fn regenerate_person_id(id: String) -> Result<Person, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let people = crate::db::iter_people()?;
+    let mut person = people.iter().find(|p| p.id == id).cloned()
+        .ok_or_else(|| ServerFnError::new(format!("No person with ID {}", id)))?;
+    person.id = generate_id(&people);
+    person.former_ids.push(id.clone());
+    crate::db::replace_person(&id, &person)?;
+    Ok(person)
+}
+
+#[server]
+pub async fn import_people(csv: String) -> Result<ImportSummary, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    import(&csv).map_err(ServerFnError::new)
+}
@@ -0,0 +1,362 @@
+/// Staged rollouts: when an environment's ref moves to a new commit, only the
+/// canary hosts get it until enough of them report back clean. A canary
+/// failure halts the rollout and everyone stays on the last good commit.
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Rollout {
+    /// Commit every host gets. `None` until the environment is first served.
+    pub stable: Option<String>,
+    /// Newer commit that only the canaries get while it's being tried.
+    pub candidate: Option<String>,
+    /// Canary hosts that have applied `candidate` cleanly.
+    #[serde(default)]
+    pub passed: BTreeSet<String>,
+    /// A canary failed on `candidate`. It stays held back until a newer
+    /// commit arrives or the rollout is resumed.
+    pub halted: bool,
+    /// Unix time `candidate` was first seen.
+    pub started: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RolloutInfo {
+    /// `None` for the default bundle.
+    pub environment: Option<String>,
+    pub rollout: Rollout,
+    /// Canaries that must pass before `candidate` is promoted.
+    pub needed: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RolloutsOverview {
+    /// Hostname patterns from `[rollout] canaries`.
+    pub canaries: Vec<String>,
+    pub promote_after: u32,
+    pub rollouts: Vec<RolloutInfo>,
+}
+
+/// What a canary report did to a rollout, when it's worth an alert.
+#[cfg(feature = "server")]
+#[derive(Debug, PartialEq)]
+enum Transition {
+    Promoted,
+    Halted,
+}
+
+#[cfg(feature = "server")]
+impl Rollout {
+    /// Follow the environment's ref to `head`. A commit we haven't seen goes
+    /// to the canaries first if `staged` says there are any, otherwise to
+    /// everyone. Returns whether a rollout started; on error nothing changes.
+    fn advance(&mut self, head: &str, staged: impl FnOnce() -> Result<bool, ServerFnError>) -> Result<bool, ServerFnError> {
+        if self.stable.as_deref() == Some(head) {
+            // The ref was moved back, which abandons any rollout in progress.
+            self.candidate = None;
+            self.passed.clear();
+            self.halted = false;
+        } else if self.candidate.as_deref() != Some(head) {
+            // The very first commit has nothing to be staged against.
+            if self.stable.is_some() && staged()? {
+                self.candidate = Some(head.to_string());
+                self.passed.clear();
+                self.halted = false;
+                self.started = chrono::Local::now().timestamp();
+                return Ok(true);
+            }
+            *self = Rollout { stable: Some(head.to_string()), ..Rollout::default() };
+        }
+        Ok(false)
+    }
+
+    /// Count a canary's report on `commit`, promoting `candidate` once
+    /// `needed` different canaries have passed or halting it on a failure.
+    /// Reports on any other commit, or while halted, don't count.
+    fn record(&mut self, hostname: &str, commit: &str, failed: bool, needed: u32) -> Option<Transition> {
+        if self.halted || self.candidate.as_deref() != Some(commit) {
+            return None;
+        }
+        if failed {
+            self.halted = true;
+            return Some(Transition::Halted);
+        }
+        self.passed.insert(hostname.to_ascii_lowercase());
+        if self.passed.len() < needed as usize {
+            return None;
+        }
+        self.stable = self.candidate.take();
+        self.passed.clear();
+        Some(Transition::Promoted)
+    }
+}
+
+/// Rollouts are stored per environment, with "" for the default bundle.
+#[cfg(feature = "server")]
+pub fn key(environment: Option<&str>) -> &str {
+    environment.unwrap_or("")
+}
+
+#[cfg(feature = "server")]
+fn environment_name(environment: Option<&str>) -> String {
+    environment.unwrap_or("default").to_string()
+}
+
+#[cfg(feature = "server")]
+pub fn is_canary(hostname: &str) -> bool {
+    crate::config::CONFIG.rollout.canaries.iter().any(|p| crate::groups::pattern_matches(p, hostname))
+}
+
+/// How many hosts known to be in `environment` are canaries. Without one a
+/// rollout could never finish, so commits go straight to everyone.
+#[cfg(feature = "server")]
+fn canary_count(environment: Option<&str>) -> Result<u32, ServerFnError> {
+    if crate::config::CONFIG.rollout.canaries.is_empty() {
+        return Ok(0);
+    }
+    let groups = crate::db::iter_groups()?;
+    let count = crate::db::iter_last_seen()?.iter()
+        .filter(|(hostname, _)| {
+            is_canary(hostname)
+                && crate::groups::group_for(&groups, hostname).map(|g| g.environment.as_str()) == environment
+        })
+        .count();
+    Ok(count as u32)
+}
+
+/// Canaries that must pass before a commit is promoted: `promote_after`, or
+/// every canary if the environment has fewer.
+#[cfg(feature = "server")]
+fn needed(environment: Option<&str>) -> Result<u32, ServerFnError> {
+    Ok(crate::config::CONFIG.rollout.promote_after.min(canary_count(environment)?).max(1))
+}
+
+#[cfg(feature = "server")]
+fn send_alert(alert: crate::alerts::Alert) {
+    tokio::spawn(async move {
+        crate::alerts::send(&alert).await;
+    });
+}
+
+/// Record that `environment`'s ref points at `head`, starting a rollout if
+/// it's a commit we haven't seen. Returns the rollout as it stands.
+#[cfg(feature = "server")]
+pub fn observe(environment: Option<&str>, head: &str) -> Result<Rollout, ServerFnError> {
+    use crate::alerts::Alert;
+
+    // Canaries are only looked up once the rollout, as read inside the
+    // transaction, shows there's something new to roll out.
+    let mut started = Ok(false);
+    let rollout = crate::db::update_rollout(key(environment), |r| {
+        started = r.advance(head, || Ok(canary_count(environment)? > 0));
+    })?;
+
+    if started? {
+        send_alert(Alert::RolloutStarted { environment: environment_name(environment), commit: head.to_string() });
+    }
+    Ok(rollout)
+}
+
+/// The commit `hostname` gets under `rollout`. Hosts that didn't say who they
+/// are get the stable commit.
+#[cfg(feature = "server")]
+pub fn served_commit<'a>(rollout: &'a Rollout, hostname: Option<&str>) -> Option<&'a str> {
+    match &rollout.candidate {
+        Some(candidate) if !rollout.halted && hostname.is_some_and(is_canary) => Some(candidate),
+        _ => rollout.stable.as_deref(),
+    }
+}
+
+/// Count a report towards the rollout of the commit it applied, promoting or
+/// halting it as needed.
+#[cfg(feature = "server")]
+pub async fn on_report(hostname: String, status: String, commit: Option<String>) {
+    if let Err(e) = process_report(&hostname, &status, commit) {
+        println!("Failed to update rollout for {}: {}", hostname, e);
+    }
+}
+
+#[cfg(feature = "server")]
+fn process_report(hostname: &str, status: &str, commit: Option<String>) -> Result<(), ServerFnError> {
+    use crate::alerts::{self, Alert};
+
+    let Some(commit) = commit else { return Ok(()) };
+    let failed = alerts::is_failure(status);
+    if !is_canary(hostname) || !(failed || alerts::is_healthy(status)) {
+        return Ok(());
+    }
+    let environment = crate::groups::environment_for(hostname)?;
+    let environment = environment.as_deref();
+    let needed = needed(environment)?;
+
+    let mut transition = None;
+    crate::db::update_rollout(key(environment), |r| {
+        transition = r.record(hostname, &commit, failed, needed);
+    })?;
+
+    match transition {
+        Some(Transition::Halted) => send_alert(Alert::RolloutHalted {
+            environment: environment_name(environment),
+            commit,
+            hostname: hostname.to_string(),
+            status: status.to_string(),
+        }),
+        Some(Transition::Promoted) => send_alert(Alert::RolloutPromoted { environment: environment_name(environment), commit }),
+        None => {}
+    }
+    Ok(())
+}
+
+#[server]
+pub async fn list_rollouts() -> Result<RolloutsOverview, ServerFnError> {
+    use crate::config::CONFIG;
+
+    crate::auth::require_role(crate::auth::Role::StudentLead).await?;
+    let rollouts = crate::db::iter_rollouts()?.into_iter()
+        .map(|(key, rollout)| {
+            let environment = Some(key).filter(|k| !k.is_empty());
+            Ok(RolloutInfo { needed: needed(environment.as_deref())?, environment, rollout })
+        })
+        .collect::<Result<_, ServerFnError>>()?;
+    Ok(RolloutsOverview {
+        canaries: CONFIG.rollout.canaries.clone(),
+        promote_after: CONFIG.rollout.promote_after,
+        rollouts,
+    })
+}
+
+/// Send the commit being tried to every host without waiting for the canaries.
+#[server]
+pub async fn promote_rollout(environment: Option<String>) -> Result<(), ServerFnError> {
+    let user = crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let environment = environment.as_deref();
+    crate::db::update_rollout(key(environment), |r| {
+        if let Some(candidate) = r.candidate.take() {
+            *r = Rollout { stable: Some(candidate), ..Rollout::default() };
+        }
+    })?;
+    println!("{} promoted the {} rollout", user.username, environment_name(environment));
+    Ok(())
+}
+
+/// Give the canaries another try at a halted commit.
+#[server]
+pub async fn resume_rollout(environment: Option<String>) -> Result<(), ServerFnError> {
+    let user = crate::auth::require_role(crate::auth::Role::Mentor).await?;
+    let environment = environment.as_deref();
+    crate::db::update_rollout(key(environment), |r| {
+        r.halted = false;
+        r.passed.clear();
+    })?;
+    println!("{} resumed the {} rollout", user.username, environment_name(environment));
+    Ok(())
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    fn rolling_out(stable: &str, candidate: &str) -> Rollout {
+        Rollout {
+            stable: Some(stable.to_string()),
+            candidate: Some(candidate.to_string()),
+            started: 1,
+            ..Rollout::default()
+        }
+    }
+
+    fn staged(has_canaries: bool) -> impl FnOnce() -> Result<bool, ServerFnError> {
+        move || Ok(has_canaries)
+    }
+
+    #[test]
+    fn first_commit_goes_to_everyone() {
+        let mut rollout = Rollout::default();
+        assert!(!rollout.advance("aaa", || panic!("no canary lookup for the first commit")).unwrap());
+        assert_eq!(rollout, Rollout { stable: Some("aaa".to_string()), ..Rollout::default() });
+    }
+
+    #[test]
+    fn new_commit_goes_to_canaries_first() {
+        let mut rollout = Rollout { stable: Some("aaa".to_string()), ..Rollout::default() };
+        assert!(rollout.advance("bbb", staged(true)).unwrap());
+        assert_eq!(rollout.stable.as_deref(), Some("aaa"));
+        assert_eq!(rollout.candidate.as_deref(), Some("bbb"));
+        assert!(!rollout.halted);
+    }
+
+    #[test]
+    fn new_commit_without_canaries_goes_to_everyone() {
+        let mut rollout = rolling_out("aaa", "bbb");
+        assert!(!rollout.advance("ccc", staged(false)).unwrap());
+        assert_eq!(rollout, Rollout { stable: Some("ccc".to_string()), ..Rollout::default() });
+    }
+
+    #[test]
+    fn same_commit_changes_nothing() {
+        let mut rollout = rolling_out("aaa", "bbb");
+        rollout.passed.insert("dev-1".to_string());
+        let before = rollout.clone();
+        assert!(!rollout.advance("bbb", || panic!("no canary lookup for a known commit")).unwrap());
+        assert_eq!(rollout, before);
+    }
+
+    #[test]
+    fn moving_back_to_stable_abandons_the_rollout() {
+        let mut rollout = rolling_out("aaa", "bbb");
+        rollout.halted = true;
+        rollout.advance("aaa", staged(true)).unwrap();
+        assert_eq!(rollout.candidate, None);
+        assert!(!rollout.halted);
+        assert!(rollout.passed.is_empty());
+    }
+
+    #[test]
+    fn failed_canary_lookup_leaves_rollout_alone() {
+        let mut rollout = rolling_out("aaa", "bbb");
+        let before = rollout.clone();
+        assert!(rollout.advance("ccc", || Err(ServerFnError::new("db"))).is_err());
+        assert_eq!(rollout, before);
+    }
+
+    #[test]
+    fn promotes_once_enough_distinct_canaries_pass() {
+        let mut rollout = rolling_out("aaa", "bbb");
+        assert_eq!(rollout.record("DEV-1", "bbb", false, 2), None);
+        // The same host reporting again, in any case, doesn't count twice.
+        assert_eq!(rollout.record("DEV-1", "bbb", false, 2), None);
+        assert_eq!(rollout.record("dev-1", "bbb", false, 2), None);
+        assert_eq!(rollout.passed.len(), 1);
+
+        assert_eq!(rollout.record("DEV-2", "bbb", false, 2), Some(Transition::Promoted));
+        assert_eq!(rollout.stable.as_deref(), Some("bbb"));
+        assert_eq!(rollout.candidate, None);
+        assert!(rollout.passed.is_empty());
+    }
+
+    #[test]
+    fn canary_failure_halts_the_rollout() {
+        let mut rollout = rolling_out("aaa", "bbb");
+        rollout.record("DEV-1", "bbb", false, 2);
+        assert_eq!(rollout.record("DEV-2", "bbb", true, 2), Some(Transition::Halted));
+        assert!(rollout.halted);
+        assert_eq!(rollout.stable.as_deref(), Some("aaa"));
+
+        // Later passes don't count while halted.
+        assert_eq!(rollout.record("DEV-3", "bbb", false, 2), None);
+        assert_eq!(rollout.candidate.as_deref(), Some("bbb"));
+    }
+
+    #[test]
+    fn reports_on_other_commits_are_ignored() {
+        let mut rollout = rolling_out("aaa", "bbb");
+        assert_eq!(rollout.record("DEV-1", "aaa", true, 1), None);
+        assert_eq!(rollout.record("DEV-1", "zzz", false, 1), None);
+        assert_eq!(rollout, rolling_out("aaa", "bbb"));
+
+        let mut settled = Rollout { stable: Some("aaa".to_string()), ..Rollout::default() };
+        assert_eq!(settled.record("DEV-1", "aaa", true, 1), None);
+        assert!(!settled.halted);
+    }
+}
@@ -1,7 +1,44 @@
 /// Puppet sync data types, ingestion, and query logic.
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "server")]
+use crate::checkins::CheckinEntry;
+#[cfg(feature = "server")]
+use crate::hosts::HostFacts;
+
+/// Resource counts from the client's `last_run_summary.yaml`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ResourceCounts {
+    pub total: u32,
+    pub changed: u32,
+    pub failed: u32,
+    pub skipped: u32,
+    pub out_of_sync: u32,
+    pub corrective_change: u32,
+    pub restarted: u32,
+    pub failed_to_restart: u32,
+    pub scheduled: u32,
+}
+
+/// One change, failure or skip puppet recorded against a resource.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResourceEvent {
+    pub resource: String,
+    pub property: Option<String>,
+    pub status: String,
+    pub message: String,
+}
+
+/// Structured results of a puppet run, parsed on the client.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct RunReport {
+    pub resources: ResourceCounts,
+    pub events: Vec<ResourceEvent>,
+    /// Seconds spent per resource type, plus `total` and `config_retrieval`.
+    pub timing: BTreeMap<String, f64>,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PuppetStatus {
@@ -11,120 +48,264 @@ pub struct PuppetStatus {
     pub timestamp: String,
     #[serde(default)]
     pub logs: String,
-    #[serde(default)]
+    /// Only on records stored before checkins got their own table.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub checkin_logs: Vec<String>,
+    /// Cron expression the client was running on when it reported.
+    #[serde(default)]
+    pub schedule: Option<String>,
+    #[serde(default)]
+    pub report: Option<RunReport>,
+    /// Manifest repository commit the applied bundle was built from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The client couldn't reach us and applied its last-known-good bundle.
+    #[serde(default)]
+    pub offline: bool,
+}
+
+/// Returned to the client once a report is stored.
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SyncAck {
+    /// Checkins that are safely stored and can be archived on the client.
+    pub checkin_ids: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SyncTableData {
     pub times: Vec<String>,
+    /// Interval index of each entry in `times`.
+    pub intervals: Vec<i64>,
     pub hostnames: Vec<String>,
     pub syncs: HashMap<String, HashMap<String, String>>,
+    /// Hosts whose run shown in each `syncs` interval was applied offline.
+    pub offline: HashMap<String, HashSet<String>>,
+    /// Commit each host in `hostnames` last applied.
+    pub commits: Vec<Option<String>>,
+    /// Commit each host in `hostnames` would be served now, when serving from git.
+    pub served_commits: Vec<Option<String>>,
+}
+
+impl SyncTableData {
+    /// Whether `hostname`'s run shown at `time` was applied offline.
+    pub fn is_offline(&self, time: &str, hostname: &str) -> bool {
+        self.offline.get(time).is_some_and(|hosts| hosts.contains(hostname))
+    }
 }
 
+/// Sync records for one host within one sync table interval.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IntervalLogs {
+    pub time: String,
+    pub logs: Vec<PuppetStatus>,
+}
+
+#[cfg(feature = "server")]
+use chrono::Local;
+
+/// Length of one sync table column.
 #[cfg(feature = "server")]
-use chrono::{Local, NaiveDateTime};
+const INTERVAL_SECS: i64 = 15 * 60;
+
+/// Intervals shown in the sync table.
 #[cfg(feature = "server")]
-use std::collections::{BTreeMap, HashSet};
+const TABLE_INTERVALS: usize = 20;
 
 /// Compute the 15-minute interval index for a timestamp string (YYYYMMDDHHmmSS).
 #[cfg(feature = "server")]
-fn interval_index(timestamp: &str) -> Option<i64> {
-    let dt = NaiveDateTime::parse_from_str(timestamp, "%Y%m%d%H%M%S").ok()?;
-    Some(dt.and_utc().timestamp() / 60 / 15)
+pub(crate) fn interval_index(timestamp: &str) -> Option<i64> {
+    Some(crate::db::timestamp_key(timestamp)?.div_euclid(INTERVAL_SECS))
 }
 
 /// Format an interval index as a display string.
 #[cfg(feature = "server")]
-fn format_interval(idx: i64) -> Option<String> {
-    Some(chrono::DateTime::from_timestamp(idx * 15 * 60, 0)?
+pub(crate) fn format_interval(idx: i64) -> Option<String> {
+    Some(chrono::DateTime::from_timestamp(idx * INTERVAL_SECS, 0)?
         .with_timezone(&Local)
         .format("%-I:%M %p %-m-%-d-%y")
         .to_string())
 }
 
+/// A puppet run as reported by a client to `/puppet-sync`.
+///
+/// Clients replay reports that failed to send, so a `report_id` seen before is
+/// acknowledged without being stored again. `ran_at` places replayed reports
+/// at the time the run actually happened. `checkin_logs` is what clients sent
+/// before checkins carried IDs. `facts` replaces the host's stored inventory
+/// unless a newer report already did. `commit` is the manifest repository
+/// commit the host applied, and `offline` says it was applied from the
+/// client's cached bundle while we were unreachable.
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncReport {
+    pub hostname: String,
+    pub status: String,
+    pub exit_code: i32,
+    pub logs: String,
+    #[serde(default)]
+    pub checkins: Option<Vec<CheckinEntry>>,
+    #[serde(default)]
+    pub checkin_logs: Option<Vec<String>>,
+    #[serde(default)]
+    pub report_id: Option<String>,
+    #[serde(default)]
+    pub ran_at: Option<i64>,
+    #[serde(default)]
+    pub schedule: Option<String>,
+    #[serde(default)]
+    pub report: Option<RunReport>,
+    #[serde(default)]
+    pub facts: Option<HostFacts>,
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub offline: bool,
+}
+
 /// Ingest a puppet sync report.
+///
+/// Every checkin in the report is acknowledged once it's stored, whether by
+/// this report or an earlier one. Clients call this, not the dashboard, so it
+/// takes the device token for the report's hostname instead of a login.
+#[cfg(feature = "server")]
 #[post("/puppet-sync")]
-pub async fn handle_sync(
-    hostname: String,
-    status: String,
-    exit_code: i32,
-    logs: String,
-    checkin_logs: Vec<String>,
-) -> Result<(), ServerFnError> {
+pub async fn handle_sync(report: dioxus::fullstack::Json<SyncReport>) -> Result<SyncAck, ServerFnError> {
+    let SyncReport {
+        hostname, status, exit_code, logs, checkins, checkin_logs,
+        report_id, ran_at, schedule, report, facts, commit, offline,
+    } = report.0;
+    crate::devices::authorize_host(&hostname).await?;
+
     let now = Local::now();
-    let timestamp = now.format("%Y%m%d%H%M%S").to_string();
+    let run_time = ran_at
+        .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+        .map(|t| t.with_timezone(&Local))
+        .filter(|t| *t <= now)
+        .unwrap_or(now);
+    let timestamp = run_time.format("%Y%m%d%H%M%S").to_string();
 
     let record = PuppetStatus {
         hostname: hostname.clone(),
-        status, exit_code,
+        status: status.clone(),
+        exit_code,
         timestamp: timestamp.clone(),
-        logs, checkin_logs,
+        logs,
+        checkin_logs: Vec::new(),
+        schedule,
+        report,
+        commit: commit.clone(),
+        offline,
     };
 
-    let json = serde_json::to_string(&record).map_err(|e| ServerFnError::new(e.to_string()))?;
-    let key = format!("sync:{}:{}", timestamp, hostname);
-    crate::db::insert_sync(&key, &json)
+    let legacy_checkins = checkin_logs.unwrap_or_default().into_iter()
+        .map(|log| CheckinEntry { id: CheckinEntry::legacy_id(&hostname, &log), log });
+    let checkins: Vec<CheckinEntry> = checkins.unwrap_or_default().into_iter().chain(legacy_checkins).collect();
+    if !crate::db::insert_sync(record, report_id.as_deref(), &checkins)? {
+        println!("Ignoring duplicate report {:?} from {}", report_id, hostname);
+    } else {
+        tokio::spawn(crate::alerts::on_report(hostname.clone(), status.clone(), exit_code, timestamp.clone()));
+        tokio::spawn(crate::rollouts::on_report(hostname.clone(), status, commit));
+        if let Some(facts) = facts {
+            crate::db::upsert_facts(&crate::hosts::HostInventory { hostname, timestamp, facts })?;
+        }
+    }
+    Ok(SyncAck {
+        checkin_ids: checkins.into_iter().map(|c| c.id).collect(),
+    })
+}
+
+/// Unix time of the next scheduled sync, going by the most recently reported schedule.
+#[server]
+pub async fn get_next_sync() -> Result<Option<i64>, ServerFnError> {
+    use std::str::FromStr;
+
+    crate::auth::require_role(crate::auth::Role::Viewer).await?;
+
+    let hosts = crate::db::iter_last_seen()?;
+    let Some(schedule) = hosts.iter()
+        .map(|(_, seen)| seen)
+        .filter(|seen| seen.schedule.is_some())
+        .max_by(|a, b| a.timestamp.cmp(&b.timestamp))
+        .and_then(|seen| seen.schedule.as_deref())
+    else {
+        return Ok(None);
+    };
+
+    let schedule = cron::Schedule::from_str(schedule).map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(schedule.upcoming(Local).next().map(|t| t.timestamp()))
 }
 
 /// Build the sync overview table (last 20 intervals x hostnames).
+///
+/// Reads runs newest first and stops once it's past the 20th interval.
 #[server]
 pub async fn get_sync_table() -> Result<SyncTableData, ServerFnError> {
-    let all = crate::db::iter_all_syncs()?;
-
-    let mut hostnames_set = HashSet::new();
-    let mut by_interval: BTreeMap<(i64, String), (String, String)> = BTreeMap::new();
-
-    for s in &all {
-        hostnames_set.insert(s.hostname.clone());
-        let Some(idx) = interval_index(&s.timestamp) else { continue };
-        let key = (idx, s.hostname.clone());
-        match by_interval.get(&key) {
-            Some((ts, _)) if *ts > s.timestamp => {}
-            _ => { by_interval.insert(key, (s.timestamp.clone(), s.status.clone())); }
-        }
-    }
+    use std::collections::hash_map::Entry;
+
+    crate::auth::require_role(crate::auth::Role::Viewer).await?;
+    let mut intervals: Vec<i64> = Vec::new();
+    // Runs arrive newest first, so the first status seen for a host in an interval wins.
+    let mut by_interval: HashMap<i64, HashMap<String, String>> = HashMap::new();
+    let mut offline_by_interval: HashMap<i64, HashSet<String>> = HashMap::new();
 
-    let mut interval_indices: Vec<i64> = by_interval.keys().map(|(idx, _)| *idx).collect();
-    interval_indices.dedup();
-    interval_indices.reverse();
-    interval_indices.truncate(20);
+    crate::db::visit_runs_rev(|s| {
+        let Some(idx) = interval_index(&s.timestamp) else { return true };
+        if intervals.last() != Some(&idx) {
+            if intervals.len() == TABLE_INTERVALS {
+                return false;
+            }
+            intervals.push(idx);
+        }
+        if let Entry::Vacant(entry) = by_interval.entry(idx).or_default().entry(s.hostname) {
+            if s.offline {
+                offline_by_interval.entry(idx).or_default().insert(entry.key().clone());
+            }
+            entry.insert(s.status);
+        }
+        true
+    })?;
 
-    let times: Vec<String> = interval_indices.iter().filter_map(|idx| format_interval(*idx)).collect();
+    let mut times = Vec::new();
     let mut syncs = HashMap::new();
-    for idx in &interval_indices {
-        let Some(display) = format_interval(*idx) else { continue };
-        let hosts: HashMap<String, String> = by_interval.iter()
-            .filter(|((i, _), _)| i == idx)
-            .map(|((_, h), (_, st))| (h.clone(), st.clone()))
-            .collect();
-        syncs.insert(display, hosts);
-    }
+    let mut offline = HashMap::new();
+    intervals.retain(|idx| match format_interval(*idx) {
+        Some(display) => {
+            syncs.insert(display.clone(), by_interval.remove(idx).unwrap_or_default());
+            offline.insert(display.clone(), offline_by_interval.remove(idx).unwrap_or_default());
+            times.push(display);
+            true
+        }
+        None => false,
+    });
 
-    let mut hostnames: Vec<String> = hostnames_set.into_iter().collect();
-    hostnames.sort();
+    let mut hosts = crate::db::iter_last_seen()?;
+    hosts.sort_by(|a, b| a.0.cmp(&b.0));
+    let (hostnames, commits): (Vec<String>, _) = hosts.into_iter()
+        .map(|(hostname, seen)| (hostname, seen.commit))
+        .unzip();
+    let served_commits = {
+        let hostnames = hostnames.clone();
+        tokio::task::spawn_blocking(move || crate::manifests::served_commits(&hostnames))
+            .await
+            .map_err(|e| ServerFnError::new(e.to_string()))?
+    };
 
-    Ok(SyncTableData { times, hostnames, syncs })
+    Ok(SyncTableData { times, intervals, hostnames, syncs, offline, commits, served_commits })
 }
 
-/// Get all sync records for a specific hostname within a display interval.
+/// Get all sync records for a specific hostname within an interval.
 #[server]
 pub async fn get_logs_for_interval(
-    time: String,
+    interval: i64,
     hostname: String,
-) -> Result<Vec<PuppetStatus>, ServerFnError> {
-    let all = crate::db::iter_all_syncs()?;
-
-    let mut results: Vec<PuppetStatus> = all.into_iter()
-        .filter(|s| {
-            s.hostname == hostname
-                && interval_index(&s.timestamp)
-                    .and_then(|idx| format_interval(idx))
-                    .map(|display| display == time)
-                    .unwrap_or(false)
-        })
-        .collect();
-
-    results.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-    Ok(results)
+) -> Result<IntervalLogs, ServerFnError> {
+    crate::auth::require_role(crate::auth::Role::StudentLead).await?;
+    let start = interval * INTERVAL_SECS;
+    let mut logs = crate::db::host_runs(&hostname, start..start + INTERVAL_SECS, usize::MAX)?;
+    crate::db::attach_logs(&mut logs)?;
+    Ok(IntervalLogs {
+        time: format_interval(interval).unwrap_or_default(),
+        logs,
+    })
 }